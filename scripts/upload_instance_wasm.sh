@@ -0,0 +1,32 @@
+#!/usr/bin/env bash
+# Uploads the apollo instance wasm to the factory by chunks
+# Usage: ./scripts/upload_instance_wasm.sh [wasm path] [network]
+set -euo pipefail
+
+WASM=${1:-assets/apollo_instance.wasm}
+NETWORK=${2:-local}
+# the ingress message is limited to 2MB, the chunk is hex escaped in the argument file
+CHUNK_SIZE=1000000
+
+TMP=$(mktemp -d)
+trap 'rm -rf "$TMP"' EXIT
+
+gzip -c -n -1 "$WASM" > "$TMP/module.gz"
+split -b $CHUNK_SIZE -d -a 4 "$TMP/module.gz" "$TMP/chunk_"
+
+CHUNKS=("$TMP"/chunk_*)
+OFFSET=0
+
+for i in "${!CHUNKS[@]}"; do
+	CHUNK=${CHUNKS[$i]}
+	IS_LAST=false
+	if [ "$i" -eq $((${#CHUNKS[@]} - 1)) ]; then
+		IS_LAST=true
+	fi
+
+	printf '(blob "%s", %d : nat64, %s)' \
+		"$(od -An -v -tx1 "$CHUNK" | tr -d ' \n' | sed 's/../\\&/g')" "$OFFSET" "$IS_LAST" > "$TMP/arg"
+	dfx canister call apollo upload_instance_wasm --argument-file "$TMP/arg" --network "$NETWORK"
+
+	OFFSET=$((OFFSET + $(wc -c < "$CHUNK")))
+done
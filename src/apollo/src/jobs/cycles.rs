@@ -0,0 +1,99 @@
+use std::{cell::RefCell, time::Duration};
+
+use apollo_utils::{
+    errors::{ApolloError, ApolloInstanceError},
+    get_metadata, log, time,
+};
+use candid::{Nat, Principal};
+use ic_cdk::api::management_canister::main::{canister_status, deposit_cycles, CanisterIdRecord};
+use ic_cdk_timers::{clear_timer, set_timer_interval, TimerId};
+
+use crate::{types::cycles::CyclesHistory, Result, STATE};
+
+thread_local! {
+    static TIMER_ID: RefCell<Option<TimerId>> = RefCell::default();
+}
+
+/// (Re)starts the cycles monitoring of the instances with the configured interval
+pub fn set_timer() {
+    TIMER_ID.with(|timer_id| {
+        if let Some(timer_id) = timer_id.borrow_mut().take() {
+            clear_timer(timer_id);
+        }
+    });
+
+    let interval = get_metadata!(cycles_check_interval_sec);
+    if interval == 0 {
+        return;
+    }
+
+    let timer_id = set_timer_interval(Duration::from_secs(interval), || {
+        ic_cdk::spawn(top_up_instances())
+    });
+
+    TIMER_ID.with(|id| *id.borrow_mut() = Some(timer_id));
+}
+
+async fn top_up_instances() {
+    let instances: Vec<(u32, Principal)> = STATE.with(|s| {
+        s.borrow()
+            .chains
+            .iter()
+            .map(|(chain_id, instance)| (chain_id, instance.0.canister_id))
+            .collect()
+    });
+
+    for (chain_id, canister_id) in instances {
+        if let Err(err) = top_up_instance(chain_id, canister_id).await {
+            log!("[CYCLES] chain: {}, unable to top up: {}", chain_id, err);
+        }
+    }
+}
+
+/// Tops up the instance from the factory balance, if its cycles are below the threshold
+async fn top_up_instance(chain_id: u32, canister_id: Principal) -> Result<()> {
+    let (status,) = canister_status(CanisterIdRecord { canister_id })
+        .await
+        .map_err(|(_, err)| ApolloError::FailedToGetCanisterStatus(err))?;
+
+    let threshold = get_metadata!(cycles_top_up_threshold);
+    let amount = get_metadata!(cycles_top_up_amount);
+
+    let result = if status.cycles < Nat::from(threshold) && amount > 0 {
+        deposit(canister_id, amount).await
+    } else {
+        Ok(0)
+    };
+
+    let topped_up = *result.as_ref().unwrap_or(&0);
+    CyclesHistory::add(
+        chain_id,
+        time::in_seconds(),
+        status.cycles.clone(),
+        Nat::from(topped_up),
+    );
+
+    if topped_up > 0 {
+        log!(
+            "[CYCLES] chain: {}, balance: {}, topped up with {} cycles",
+            chain_id,
+            status.cycles,
+            topped_up
+        );
+    }
+
+    result.map(|_| ())
+}
+
+async fn deposit(canister_id: Principal, amount: u128) -> Result<u128> {
+    let available = ic_cdk::api::canister_balance128();
+    if available < amount {
+        return Err(ApolloError::NotEnoughCycles(available, amount));
+    }
+
+    deposit_cycles(CanisterIdRecord { canister_id }, amount)
+        .await
+        .map_err(|(_, err)| ApolloInstanceError::FailedToSendCycles(err))?;
+
+    Ok(amount)
+}
@@ -6,6 +6,7 @@ use candid::Nat;
 use types::{Metadata, STATE};
 use utils::set_custom_panic_hook;
 
+mod jobs;
 mod memory;
 mod methods;
 mod migrations;
@@ -23,9 +24,12 @@ fn init(sybil_canister_address: String, key_name: String) {
             .set(Cbor(Metadata {
                 key_name,
                 sybil_canister_address,
+                ..Default::default()
             }))
             .unwrap();
     });
+
+    jobs::cycles::set_timer();
 }
 
 // For candid file auto-generation
@@ -33,9 +37,12 @@ pub type Result<T> = std::result::Result<T, ApolloError>;
 
 use crate::types::apollo_instance::*;
 use crate::types::custom_return_types::*;
-use apollo_utils::apollo_instance::UpdateMetadata;
+use crate::types::cycles::CyclesRecord;
+use crate::types::wasm::{UpgradeRollout, WasmVersion};
+use apollo_utils::apollo_instance::{CoordinatorAbiVersion, RelayedRequest, UpdateMetadata};
 use apollo_utils::pagination::*;
 use candid::Principal;
+use serde_bytes::ByteBuf;
 
 // Candid file auto-generation
 candid::export_service!();
@@ -9,6 +9,12 @@ const METADATA_MEMORY_ID: MemoryId = MemoryId::new(1);
 // A memory for the StableBTreeMap we're using. A new memory should be created for
 // every additional stable structure.
 const STABLE_BTREE_MEMORY_ID: MemoryId = MemoryId::new(2);
+// A memory for the cycles history of the instances
+const CYCLES_HISTORY_MEMORY_ID: MemoryId = MemoryId::new(3);
+// A memory for the wasm modules of the instances
+const INSTANCE_WASMS_MEMORY_ID: MemoryId = MemoryId::new(4);
+// A memory for the metadata of the instance wasms, so the modules aren't read to list the versions
+const WASM_VERSIONS_MEMORY_ID: MemoryId = MemoryId::new(5);
 
 pub type VMemory = VirtualMemory<DefaultMemoryImpl>;
 
@@ -30,3 +36,15 @@ pub fn get_stable_btree_memory() -> VMemory {
 pub fn get_metadata_memory() -> VMemory {
     MEMORY_MANAGER.with(|m| m.borrow().get(METADATA_MEMORY_ID))
 }
+
+pub fn get_cycles_history_memory() -> VMemory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(CYCLES_HISTORY_MEMORY_ID))
+}
+
+pub fn get_instance_wasms_memory() -> VMemory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(INSTANCE_WASMS_MEMORY_ID))
+}
+
+pub fn get_wasm_versions_memory() -> VMemory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(WASM_VERSIONS_MEMORY_ID))
+}
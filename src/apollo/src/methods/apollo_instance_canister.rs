@@ -1,17 +1,13 @@
 use apollo_utils::{
-    apollo_instance::{ApolloInstanceMetadata, UpdateMetadata},
+    apollo_instance::{ApolloInstanceMetadata, CoordinatorAbiVersion, UpdateMetadata},
     canister::validate_caller,
     errors::{ApolloError, ApolloInstanceError},
-    nat::ToNativeTypes,
     retry_until_success,
 };
 use candid::{candid_method, Nat, Principal};
 use ic_cdk::update;
 
-use crate::{
-    types::{custom_return_types::StringResult, STATE},
-    ApolloInstanceMetadataResult, Result,
-};
+use crate::{types::custom_return_types::StringResult, ApolloInstanceMetadataResult, Result};
 
 #[candid_method]
 #[update]
@@ -60,7 +56,7 @@ async fn update_apollo_instance_metadata(
     update_metadata_args: UpdateMetadata,
 ) -> Result<()> {
     validate_caller()?;
-    let apollo_instance = crate::get_apollo_instance!(chain_id.clone());
+    let apollo_instance = crate::get_apollo_instance!(chain_id);
 
     let (result,): (std::result::Result<(), ApolloInstanceError>,) =
         retry_until_success!(ic_cdk::call(
@@ -70,18 +66,6 @@ async fn update_apollo_instance_metadata(
         ))
         .map_err(|(_, msg)| ApolloError::CommunicationWithApolloInstanceFailed(msg))?;
 
-    // TODO: DELETE
-    STATE.with(|s| {
-        let mut state = s.borrow_mut();
-        let mut chain = state.chains.get(&chain_id.to_u32()).unwrap();
-
-        if let Some(apollo_coordinator) = update_metadata_args.apollo_coordinator {
-            chain.0.apollo_coordinator = apollo_coordinator;
-        }
-
-        state.chains.insert(chain_id.to_u32(), chain);
-    });
-
     Ok(result?)
 }
 
@@ -105,6 +89,45 @@ async fn update_last_parsed_logs_from_block(
     Ok(result?)
 }
 
+#[candid_method]
+#[update]
+async fn add_coordinator(
+    chain_id: Nat,
+    address: String,
+    abi_version: Option<CoordinatorAbiVersion>,
+    from_block: Option<u64>,
+) -> Result<()> {
+    validate_caller()?;
+    let apollo_instance = crate::get_apollo_instance!(chain_id);
+
+    let (result,): (std::result::Result<(), ApolloInstanceError>,) =
+        retry_until_success!(ic_cdk::call(
+            apollo_instance.canister_id,
+            "add_coordinator",
+            (address.clone(), abi_version, from_block)
+        ))
+        .map_err(|(_, msg)| ApolloError::CommunicationWithApolloInstanceFailed(msg))?;
+
+    Ok(result?)
+}
+
+#[candid_method]
+#[update]
+async fn remove_coordinator(chain_id: Nat, address: String) -> Result<()> {
+    validate_caller()?;
+    let apollo_instance = crate::get_apollo_instance!(chain_id);
+
+    let (result,): (std::result::Result<(), ApolloInstanceError>,) =
+        retry_until_success!(ic_cdk::call(
+            apollo_instance.canister_id,
+            "remove_coordinator",
+            (address.clone(),)
+        ))
+        .map_err(|(_, msg)| ApolloError::CommunicationWithApolloInstanceFailed(msg))?;
+
+    Ok(result?)
+}
+
 #[candid_method]
 #[update]
 async fn update_timer_frequency_sec(chain_id: Nat, timer_frequency_sec: u64) -> Result<()> {
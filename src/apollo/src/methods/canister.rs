@@ -1,6 +1,9 @@
+use crate::jobs;
 use crate::methods::apollo_instance_canister::send_cycles;
 use crate::methods::INIT_CYCLES_BALANCE;
 use crate::types::apollo_instance::AddApolloInstanceRequest;
+use crate::types::cycles::{CyclesHistory, CyclesRecord};
+use crate::types::wasm::InstanceWasms;
 use crate::types::UpdateMetadata;
 use crate::types::{apollo_instance::ApolloInstance, Metadata, STATE};
 use apollo_utils::apollo_instance::ApolloInstanceInit;
@@ -41,6 +44,9 @@ fn update_metadata(update_metadata_args: UpdateMetadata) -> Result<()> {
         state.metadata.set(Cbor(metadata)).unwrap();
     });
 
+    // the check interval could be changed
+    jobs::cycles::set_timer();
+
     Ok(())
 }
 
@@ -109,6 +115,14 @@ async fn add_apollo_instance(req: AddApolloInstanceRequest) -> Result<()> {
         return Err(ApolloError::ChainAlreadyExists(chain_id).into());
     }
 
+    let wasm_version = match req.wasm_version {
+        Some(version) => version,
+        None => InstanceWasms::latest_version().ok_or(ApolloError::NoInstanceWasm)?,
+    };
+    let wasm = InstanceWasms::get(wasm_version)
+        .ok_or(ApolloError::WasmVersionNotFound(wasm_version))?
+        .module;
+
     let canister_id = match create_canister(
         CreateCanisterArgument { settings: None },
         INIT_CYCLES_BALANCE,
@@ -121,8 +135,6 @@ async fn add_apollo_instance(req: AddApolloInstanceRequest) -> Result<()> {
         }
     };
 
-    let wasm = include_bytes!("../../../../assets/apollo_instance.wasm").to_vec();
-
     let payload = (ApolloInstanceInit {
         chain_id: chain_id.clone(),
         apollos_fee: req.apollos_fee,
@@ -140,7 +152,7 @@ async fn add_apollo_instance(req: AddApolloInstanceRequest) -> Result<()> {
     match install_code(InstallCodeArgument {
         mode: CanisterInstallMode::Install,
         canister_id,
-        wasm_module: wasm.to_vec(),
+        wasm_module: wasm,
         arg: encode_args(payload).unwrap(),
     })
     .await
@@ -162,9 +174,10 @@ async fn add_apollo_instance(req: AddApolloInstanceRequest) -> Result<()> {
                 let apollo_instance = ApolloInstance {
                     canister_id,
                     is_active: false,
-                    apollo_coordinator: req.apollo_coordinator,
                     apollo_main_address,
                     chain_id: chain_id.clone(),
+                    wasm_version: Some(wasm_version),
+                    previous_wasm_version: None,
                 };
 
                 state
@@ -270,39 +283,14 @@ async fn remove_apollo_instance(chain_id: Nat) -> Result<()> {
     STATE.with(|s| {
         s.borrow_mut().chains.remove(&chain_id.to_u32());
     });
+    CyclesHistory::remove(chain_id.to_u32());
 
     Ok(())
 }
 
+/// Get the latest cycles checks of the instance, the oldest first
 #[candid_method]
-#[update]
-pub async fn upgrade_chains() -> Result<()> {
-    log!("Updating apollo instances");
-
-    let wasm = include_bytes!("../../../../assets/apollo_instance.wasm").to_vec();
-    let apollo_instances = get_apollo_instances(None).items;
-
-    for GetApolloInstanceResult {
-        chain_id,
-        apollo_instance,
-    } in apollo_instances
-    {
-        match install_code(InstallCodeArgument {
-            mode: CanisterInstallMode::Upgrade,
-            canister_id: apollo_instance.canister_id,
-            wasm_module: wasm.to_vec(),
-            arg: vec![],
-        })
-        .await
-        {
-            Ok(()) => {
-                log!("Apollo instance upgraded: {}", chain_id);
-            }
-            Err((_, error)) => {
-                return Err(ApolloInstanceError::FailedToUpgrade(error.to_string()).into());
-            }
-        }
-    }
-
-    Ok(())
+#[query]
+fn get_cycles_history(chain_id: Nat) -> Vec<CyclesRecord> {
+    CyclesHistory::get(chain_id.to_u32())
 }
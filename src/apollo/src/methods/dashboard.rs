@@ -0,0 +1,147 @@
+use apollo_utils::{
+    apollo_instance::{ApolloInstanceStats, SolvencyStatus},
+    canister::validate_caller,
+    errors::{ApolloError, ApolloInstanceError},
+    retry_until_success,
+};
+use candid::{candid_method, Nat, Principal};
+use futures::future::join_all;
+use ic_cdk::update;
+
+use crate::{
+    types::{
+        custom_return_types::{ChainBalance, ChainSolvency, ChainStats},
+        STATE,
+    },
+    Result,
+};
+
+/// Get balances of the user on every chain, instances are requested concurrently.
+/// Every call fans out to all the instances, so it is restricted to the controllers
+///
+/// # Arguments
+/// * `address` - Address of the user, for example 0x1234567890abcdef1234567890abcdef12345678
+///
+/// # Returns
+///
+/// Returns the balance or the error per chain
+#[candid_method]
+#[update]
+pub async fn get_user_balances(address: String) -> Result<Vec<ChainBalance>> {
+    validate_caller()?;
+
+    let instances = get_instances();
+
+    let balances = join_all(
+        instances
+            .iter()
+            .map(|(_, canister_id)| get_instance_balance(*canister_id, address.clone())),
+    )
+    .await;
+
+    Ok(instances
+        .into_iter()
+        .zip(balances)
+        .map(|((chain_id, _), balance)| ChainBalance {
+            chain_id,
+            balance: balance.into(),
+        })
+        .collect())
+}
+
+/// Get totals of the deposits, fees and gas spent alongside the AMA balance on every chain,
+/// instances are requested concurrently. Restricted to the controllers
+#[candid_method]
+#[update]
+pub async fn get_chains_stats() -> Result<Vec<ChainStats>> {
+    validate_caller()?;
+
+    let instances = get_instances();
+
+    let stats = join_all(instances.iter().map(|(_, canister_id)| async move {
+        futures::join!(
+            get_instance_stats(*canister_id),
+            get_ama_balance(*canister_id)
+        )
+    }))
+    .await;
+
+    Ok(instances
+        .into_iter()
+        .zip(stats)
+        .map(|((chain_id, _), (stats, ama_balance))| ChainStats {
+            chain_id,
+            stats: stats.into(),
+            ama_balance: ama_balance.into(),
+        })
+        .collect())
+}
+
+/// Get the AMA solvency status on every chain, instances are requested concurrently.
+/// The fulfillment is paused on the chains, where the AMA is insolvent. Restricted to the controllers
+#[candid_method]
+#[update]
+pub async fn get_chains_solvency() -> Result<Vec<ChainSolvency>> {
+    validate_caller()?;
+
+    let instances = get_instances();
+
+    let statuses = join_all(
+        instances
+            .iter()
+            .map(|(_, canister_id)| get_solvency_status(*canister_id)),
+    )
+    .await;
+
+    Ok(instances
+        .into_iter()
+        .zip(statuses)
+        .map(|((chain_id, _), status)| ChainSolvency {
+            chain_id,
+            status: status.into(),
+        })
+        .collect())
+}
+
+/// chain id => canister id of the instance
+fn get_instances() -> Vec<(u32, Principal)> {
+    STATE.with(|s| {
+        s.borrow()
+            .chains
+            .iter()
+            .map(|(chain_id, instance)| (chain_id, instance.0.canister_id))
+            .collect()
+    })
+}
+
+async fn get_instance_balance(canister_id: Principal, address: String) -> Result<Nat> {
+    let (result,): (std::result::Result<Nat, ApolloInstanceError>,) =
+        retry_until_success!(ic_cdk::call(canister_id, "get_balance", (address.clone(),)))
+            .map_err(|(_, msg)| ApolloError::CommunicationWithApolloInstanceFailed(msg))?;
+
+    Ok(result?)
+}
+
+async fn get_instance_stats(canister_id: Principal) -> Result<ApolloInstanceStats> {
+    let (stats,): (ApolloInstanceStats,) =
+        retry_until_success!(ic_cdk::call(canister_id, "get_stats", ()))
+            .map_err(|(_, msg)| ApolloError::CommunicationWithApolloInstanceFailed(msg))?;
+
+    Ok(stats)
+}
+
+async fn get_ama_balance(canister_id: Principal) -> Result<Nat> {
+    let (result,): (std::result::Result<Nat, ApolloInstanceError>,) =
+        retry_until_success!(ic_cdk::call(canister_id, "get_ama_balance", ()))
+            .map_err(|(_, msg)| ApolloError::CommunicationWithApolloInstanceFailed(msg))?;
+
+    Ok(result?)
+}
+
+async fn get_solvency_status(canister_id: Principal) -> Result<Option<SolvencyStatus>> {
+    let (status,): (Option<SolvencyStatus>,) =
+        retry_until_success!(ic_cdk::call(canister_id, "get_solvency_status", ()))
+            .map_err(|(_, msg)| ApolloError::CommunicationWithApolloInstanceFailed(msg))?;
+
+    Ok(status)
+}
@@ -2,6 +2,9 @@ mod allowances;
 mod apollo_instance_canister;
 mod balances;
 pub mod canister;
+mod dashboard;
 mod execution;
+mod relay;
+mod upgrades;
 
 const INIT_CYCLES_BALANCE: u128 = 500_000_000_000;
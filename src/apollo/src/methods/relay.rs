@@ -0,0 +1,87 @@
+use apollo_utils::{
+    apollo_instance::RelayedRequest,
+    errors::{ApolloError, ApolloInstanceError},
+    log, retry_until_success,
+};
+use candid::{candid_method, Nat};
+use ic_cdk::update;
+
+use crate::{NatResult, Result};
+
+/// Route the cross-chain request to the instance of the target chain
+///
+/// # Arguments
+///
+/// * `request` - Request, which was charged by the instance of the source chain, the caller must be that instance
+///
+/// # Returns
+///
+/// Returns the id of the request on the target chain
+#[candid_method]
+#[update]
+pub async fn relay_request(request: RelayedRequest) -> Result<u64> {
+    validate_source_instance(&request.source_chain_id)?;
+
+    let target_instance = crate::get_apollo_instance!(request.target_chain_id.clone());
+
+    let (result,): (std::result::Result<u64, ApolloInstanceError>,) =
+        retry_until_success!(ic_cdk::call(
+            target_instance.canister_id,
+            "add_relayed_request",
+            (request.clone(),)
+        ))
+        .map_err(|(_, msg)| ApolloError::CommunicationWithApolloInstanceFailed(msg))?;
+
+    let relayed_request_id = result?;
+
+    log!(
+        "Request {} relayed from chain {} to chain {} as {}",
+        request.request_id,
+        request.source_chain_id,
+        request.target_chain_id,
+        relayed_request_id
+    );
+
+    Ok(relayed_request_id)
+}
+
+/// Get the gas price of the target chain, the cross-chain requests are charged at it by the source chain
+///
+/// # Arguments
+///
+/// * `source_chain_id` - Chain of the caller, the caller must be its instance
+/// * `target_chain_id` - Chain, which delivers the request
+///
+/// # Returns
+///
+/// Returns the gas price in wei of the target chain
+#[candid_method]
+#[update]
+pub async fn get_chain_gas_price(source_chain_id: Nat, target_chain_id: Nat) -> NatResult {
+    let result = async move {
+        validate_source_instance(&source_chain_id)?;
+
+        let target_instance = crate::get_apollo_instance!(target_chain_id);
+
+        let (result,): (std::result::Result<Nat, ApolloInstanceError>,) = retry_until_success!(
+            ic_cdk::call(target_instance.canister_id, "get_gas_price", ())
+        )
+        .map_err(|(_, msg)| ApolloError::CommunicationWithApolloInstanceFailed(msg))?;
+
+        Ok(result?)
+    }
+    .await;
+
+    NatResult::from(result)
+}
+
+fn validate_source_instance(source_chain_id: &Nat) -> Result<()> {
+    let source_instance = crate::get_apollo_instance!(source_chain_id.clone());
+    if source_instance.canister_id != ic_cdk::caller() {
+        return Err(ApolloError::CallerIsNotApolloInstance(
+            source_chain_id.clone(),
+        ));
+    }
+
+    Ok(())
+}
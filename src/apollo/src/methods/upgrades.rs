@@ -0,0 +1,365 @@
+use apollo_utils::{
+    canister::validate_caller,
+    errors::{ApolloError, ApolloInstanceError},
+    log,
+    memory::Cbor,
+    nat::ToNativeTypes,
+    retry_until_success, time,
+};
+use candid::{candid_method, Nat, Principal};
+use ic_cdk::api::management_canister::main::{
+    install_code, start_canister, stop_canister, CanisterIdRecord, CanisterInstallMode,
+    InstallCodeArgument,
+};
+use ic_cdk::{query, update};
+use serde_bytes::ByteBuf;
+
+use crate::{
+    types::{
+        custom_return_types::ChainUpgrade,
+        wasm::{is_wasm, InstanceWasms, UpgradeRollout, WasmVersion},
+        STATE,
+    },
+    update_apollo_instance, Result,
+};
+
+/// Upload the instance wasm by chunks, the last chunk stores the module as a new version
+///
+/// # Arguments
+/// * `chunk` - Part of the module, the ingress message size is limited to 2MB
+/// * `offset` - Position of the chunk in the module, 0 restarts the upload
+/// * `is_last` - Whether the upload is finished
+///
+/// # Returns
+///
+/// Returns the stored version after the last chunk, the latest version is returned if the module is the same
+#[candid_method]
+#[update]
+pub fn upload_instance_wasm(
+    chunk: ByteBuf,
+    offset: u64,
+    is_last: bool,
+) -> Result<Option<WasmVersion>> {
+    validate_caller()?;
+
+    InstanceWasms::add_chunk(&chunk, offset)?;
+
+    if !is_last {
+        return Ok(None);
+    }
+
+    let module = InstanceWasms::take_upload();
+    if !is_wasm(&module) {
+        return Err(ApolloError::InvalidWasm);
+    }
+
+    let version = InstanceWasms::add(module, time::in_seconds());
+    let wasm = InstanceWasms::get(version).ok_or(ApolloError::WasmVersionNotFound(version))?;
+
+    log!(
+        "Instance wasm uploaded, version: {}, hash: {}",
+        version,
+        wasm.hash
+    );
+
+    Ok(Some(WasmVersion::from(&wasm)))
+}
+
+/// Upgrade the instances to the uploaded wasm, the chains are upgraded one by one
+///
+/// # Arguments
+/// * `rollout` - Chains to upgrade, a failed chain doesn't stop the upgrade of the others, unless it is the canary.
+/// The chains, which are already on the version, are skipped
+/// * `version` - Wasm version to install, the latest one by default
+///
+/// # Returns
+///
+/// Returns the upgrade result per chain
+#[candid_method]
+#[update]
+pub async fn upgrade_chains(
+    rollout: UpgradeRollout,
+    version: Option<u32>,
+) -> Result<Vec<ChainUpgrade>> {
+    validate_caller()?;
+
+    let version = match version {
+        Some(version) => version,
+        None => InstanceWasms::latest_version().ok_or(ApolloError::NoInstanceWasm)?,
+    };
+
+    log!("Upgrading apollo instances to version {}", version);
+
+    let mut reports = vec![];
+
+    let chain_ids = match rollout {
+        UpgradeRollout::All => get_chain_ids(),
+        UpgradeRollout::Chains(chain_ids) => chain_ids.iter().map(|id| id.to_u32()).collect(),
+        UpgradeRollout::Canary(canary_chain_id) => {
+            let canary = upgrade_chain(canary_chain_id.to_u32(), version).await;
+            let is_upgraded = canary.result.is_ok();
+            reports.push(canary);
+
+            let chain_ids = get_chain_ids()
+                .into_iter()
+                .filter(|chain_id| *chain_id != canary_chain_id.to_u32())
+                .collect();
+
+            if !is_upgraded {
+                log!("Canary chain {} failed, upgrade stopped", canary_chain_id);
+
+                for chain_id in chain_ids {
+                    reports.push(failed_upgrade(
+                        chain_id,
+                        Some(version),
+                        ApolloError::CanaryUpgradeFailed(canary_chain_id.clone()),
+                    ));
+                }
+
+                return Ok(reports);
+            }
+
+            chain_ids
+        }
+    };
+
+    for chain_id in chain_ids {
+        reports.push(upgrade_chain(chain_id, version).await);
+    }
+
+    Ok(reports)
+}
+
+/// Reinstall the wasm, which was installed on the chains before their last upgrade
+#[candid_method]
+#[update]
+pub async fn rollback_chains(chain_ids: Vec<Nat>) -> Result<Vec<ChainUpgrade>> {
+    validate_caller()?;
+
+    let mut reports = Vec::with_capacity(chain_ids.len());
+
+    for chain_id in chain_ids {
+        let previous_version = STATE.with(|state| {
+            state
+                .borrow()
+                .chains
+                .get(&chain_id.to_u32())
+                .and_then(|instance| instance.0.previous_wasm_version)
+        });
+
+        let report = match previous_version {
+            Some(version) => upgrade_chain(chain_id.to_u32(), version).await,
+            None => failed_upgrade(
+                chain_id.to_u32(),
+                None,
+                ApolloError::NoPreviousWasmVersion(chain_id),
+            ),
+        };
+
+        reports.push(report);
+    }
+
+    Ok(reports)
+}
+
+/// Delete the stored instance wasm, which is not installed on any chain and is not their rollback target
+///
+/// # Arguments
+/// * `version` - Wasm version to delete, the latest version can't be deleted
+///
+/// # Returns
+///
+/// Returns a result that can contain an error message
+#[candid_method]
+#[update]
+pub fn delete_instance_wasm(version: u32) -> Result<()> {
+    validate_caller()?;
+
+    InstanceWasms::remove(version)?;
+
+    log!("Instance wasm deleted, version: {}", version);
+
+    Ok(())
+}
+
+/// Get the stored instance wasm versions, the oldest first
+#[candid_method]
+#[query]
+fn get_instance_wasm_versions() -> Vec<WasmVersion> {
+    InstanceWasms::get_versions()
+}
+
+async fn upgrade_chain(chain_id: u32, version: u32) -> ChainUpgrade {
+    let from_version = get_wasm_version(chain_id);
+
+    // reinstalling the same version would overwrite the rollback target
+    if from_version == Some(version) {
+        log!(
+            "Apollo instance {} is already on version {}, upgrade skipped",
+            chain_id,
+            version
+        );
+
+        return ChainUpgrade {
+            chain_id,
+            from_version,
+            to_version: Some(version),
+            result: Ok(()),
+        };
+    }
+
+    let result = install_version(chain_id, version).await;
+
+    match &result {
+        Ok(()) => log!(
+            "Apollo instance upgraded: {}, version: {:?} -> {}",
+            chain_id,
+            from_version,
+            version
+        ),
+        Err(err) => log!(
+            "Apollo instance upgrade failed: {}, version: {}, error: {}",
+            chain_id,
+            version,
+            err
+        ),
+    }
+
+    ChainUpgrade {
+        chain_id,
+        from_version,
+        to_version: Some(version),
+        result,
+    }
+}
+
+fn failed_upgrade(chain_id: u32, to_version: Option<u32>, err: ApolloError) -> ChainUpgrade {
+    ChainUpgrade {
+        chain_id,
+        from_version: get_wasm_version(chain_id),
+        to_version,
+        result: Err(err),
+    }
+}
+
+async fn install_version(chain_id: u32, version: u32) -> Result<()> {
+    let apollo_instance = crate::get_apollo_instance!(Nat::from(chain_id));
+    let wasm = InstanceWasms::get(version).ok_or(ApolloError::WasmVersionNotFound(version))?;
+    let canister_id = apollo_instance.canister_id;
+
+    // the instance timer is stopped, so no new tick is started during the upgrade
+    if apollo_instance.is_active {
+        call_instance(canister_id, "stop").await?;
+    }
+
+    let upgraded = upgrade_stopped(canister_id, wasm.module).await;
+
+    // the timer is restarted even if the upgrade failed, so the instance keeps working on the old code.
+    // The chain could be started or stopped during the upgrade, so its current state is used
+    let started = if is_active(chain_id) {
+        call_instance(canister_id, "start").await
+    } else {
+        Ok(())
+    };
+
+    // the upgrade error is reported, even if the instance wasn't started afterwards
+    if let Err(err) = upgraded {
+        if let Err(start_err) = started {
+            log!(
+                "Apollo instance {} was not started after the failed upgrade: {}",
+                chain_id,
+                start_err
+            );
+        }
+
+        return Err(err);
+    }
+
+    // the record is read again, so the updates, which were made during the upgrade, are kept.
+    // The version is recorded even if the instance wasn't started, since the code is installed.
+    // The previous version is kept, if the installed one didn't change
+    let mut apollo_instance = crate::get_apollo_instance!(Nat::from(chain_id));
+    if apollo_instance.wasm_version != Some(version) {
+        apollo_instance.previous_wasm_version = apollo_instance.wasm_version;
+        apollo_instance.wasm_version = Some(version);
+        update_apollo_instance!(Nat::from(chain_id), apollo_instance);
+    }
+
+    started?;
+
+    // the instances relay the cross-chain requests through the factory
+    let (result,): (std::result::Result<(), ApolloInstanceError>,) = retry_until_success!(
+        ic_cdk::call(canister_id, "set_apollo_canister", (ic_cdk::id(),))
+    )
+    .map_err(|(_, msg)| ApolloError::CommunicationWithApolloInstanceFailed(msg))?;
+
+    result?;
+
+    Ok(())
+}
+
+/// Stops the canister to wait for the running tick, upgrades it and starts it again
+async fn upgrade_stopped(canister_id: Principal, module: Vec<u8>) -> Result<()> {
+    let installed: Result<()> = async {
+        stop_canister(CanisterIdRecord { canister_id })
+            .await
+            .map_err(|(_, err)| ApolloInstanceError::FailedToStop(err))?;
+
+        install_code(InstallCodeArgument {
+            mode: CanisterInstallMode::Upgrade,
+            canister_id,
+            wasm_module: module,
+            arg: vec![],
+        })
+        .await
+        .map_err(|(_, err)| ApolloInstanceError::FailedToUpgrade(err))?;
+
+        Ok(())
+    }
+    .await;
+
+    start_canister(CanisterIdRecord { canister_id })
+        .await
+        .map_err(|(_, err)| ApolloInstanceError::FailedToStart(err))?;
+
+    installed
+}
+
+async fn call_instance(canister_id: Principal, method: &str) -> Result<()> {
+    let (result,): (std::result::Result<(), ApolloInstanceError>,) =
+        retry_until_success!(ic_cdk::call(canister_id, method, ()))
+            .map_err(|(_, msg)| ApolloError::CommunicationWithApolloInstanceFailed(msg))?;
+
+    Ok(result?)
+}
+
+fn get_chain_ids() -> Vec<u32> {
+    STATE.with(|state| {
+        state
+            .borrow()
+            .chains
+            .iter()
+            .map(|(chain_id, _)| chain_id)
+            .collect()
+    })
+}
+
+fn is_active(chain_id: u32) -> bool {
+    STATE.with(|state| {
+        state
+            .borrow()
+            .chains
+            .get(&chain_id)
+            .is_some_and(|instance| instance.0.is_active)
+    })
+}
+
+fn get_wasm_version(chain_id: u32) -> Option<u32> {
+    STATE.with(|state| {
+        state
+            .borrow()
+            .chains
+            .get(&chain_id)
+            .and_then(|instance| instance.0.wasm_version)
+    })
+}
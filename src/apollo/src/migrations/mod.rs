@@ -8,7 +8,12 @@ use ic_utils::{
     monitor::{self, store::DayDataTable},
 };
 
-use crate::{memory, types::State, utils::set_custom_panic_hook, STATE};
+use crate::{
+    jobs, memory,
+    types::{wasm::WasmVersions, State},
+    utils::set_custom_panic_hook,
+    STATE,
+};
 
 // A pre-upgrade hook for serializing the data stored on the heap.
 #[pre_upgrade]
@@ -21,7 +26,11 @@ fn pre_upgrade() {
 async fn post_upgrade() {
     load_upgrade_data();
 
+    // versions of the wasms, which were uploaded before their metadata was stored separately
+    WasmVersions::migrate();
+
     set_custom_panic_hook();
+    jobs::cycles::set_timer();
     log!("Post upgrade finished");
 }
 
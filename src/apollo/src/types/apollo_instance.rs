@@ -6,10 +6,14 @@ pub struct ApolloInstance {
     pub canister_id: Principal,
     pub chain_id: Nat,
     #[serde(default)]
-    pub apollo_coordinator: String,
-    #[serde(default)]
     pub apollo_main_address: String,
     pub is_active: bool,
+    /// Installed wasm version, `None` for the instances, which were installed before the versioning
+    #[serde(default)]
+    pub wasm_version: Option<u32>,
+    /// Version, which was installed before the last upgrade, the rollback target
+    #[serde(default)]
+    pub previous_wasm_version: Option<u32>,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Debug)]
@@ -23,6 +27,9 @@ pub struct AddApolloInstanceRequest {
     pub timer_frequency_sec: u64,
     pub block_gas_limit: Nat,
     pub min_balance: Nat,
+    /// Wasm version to install, the latest uploaded one by default
+    #[serde(default)]
+    pub wasm_version: Option<u32>,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Debug)]
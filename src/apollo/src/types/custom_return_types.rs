@@ -1,6 +1,9 @@
 /// These types are created in order to generate proper name for the struct
 /// in the generated candid file.
-use apollo_utils::{apollo_instance::ApolloInstanceMetadata, errors::ApolloError};
+use apollo_utils::{
+    apollo_instance::{ApolloInstanceMetadata, ApolloInstanceStats, SolvencyStatus},
+    errors::ApolloError,
+};
 use candid::{CandidType, Nat};
 
 use super::apollo_instance::ApolloInstance;
@@ -23,8 +26,79 @@ pub enum NatResult {
     Err(ApolloError),
 }
 
+impl From<Result<Nat, ApolloError>> for NatResult {
+    fn from(result: Result<Nat, ApolloError>) -> Self {
+        match result {
+            Ok(amount) => NatResult::Ok(amount),
+            Err(err) => NatResult::Err(err),
+        }
+    }
+}
+
+#[derive(Debug, CandidType)]
+pub enum ApolloInstanceStatsResult {
+    Ok(ApolloInstanceStats),
+    Err(ApolloError),
+}
+
+impl From<Result<ApolloInstanceStats, ApolloError>> for ApolloInstanceStatsResult {
+    fn from(result: Result<ApolloInstanceStats, ApolloError>) -> Self {
+        match result {
+            Ok(stats) => ApolloInstanceStatsResult::Ok(stats),
+            Err(err) => ApolloInstanceStatsResult::Err(err),
+        }
+    }
+}
+
+#[derive(Debug, CandidType)]
+pub enum SolvencyStatusResult {
+    Ok(Option<SolvencyStatus>),
+    Err(ApolloError),
+}
+
+impl From<Result<Option<SolvencyStatus>, ApolloError>> for SolvencyStatusResult {
+    fn from(result: Result<Option<SolvencyStatus>, ApolloError>) -> Self {
+        match result {
+            Ok(status) => SolvencyStatusResult::Ok(status),
+            Err(err) => SolvencyStatusResult::Err(err),
+        }
+    }
+}
+
+/// Balance of the user on the chain
+#[derive(Debug, CandidType)]
+pub struct ChainBalance {
+    pub chain_id: u32,
+    pub balance: NatResult,
+}
+
+/// Totals of the chain alongside the native token balance of its AMA
+#[derive(Debug, CandidType)]
+pub struct ChainStats {
+    pub chain_id: u32,
+    pub stats: ApolloInstanceStatsResult,
+    pub ama_balance: NatResult,
+}
+
 #[derive(Debug, CandidType, Clone)]
 pub struct GetApolloInstanceResult {
     pub chain_id: u32,
     pub apollo_instance: ApolloInstance,
 }
+
+/// The last AMA solvency check of the chain, `None` if the AMA was not checked yet
+#[derive(Debug, CandidType)]
+pub struct ChainSolvency {
+    pub chain_id: u32,
+    pub status: SolvencyStatusResult,
+}
+
+/// Result of the instance upgrade on the chain
+#[derive(Debug, CandidType)]
+pub struct ChainUpgrade {
+    pub chain_id: u32,
+    pub from_version: Option<u32>,
+    /// `None` if there was no version to roll back to
+    pub to_version: Option<u32>,
+    pub result: Result<(), ApolloError>,
+}
@@ -0,0 +1,105 @@
+use apollo_utils::memory::Cbor;
+use candid::{CandidType, Nat};
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+
+use crate::memory::VMemory;
+
+use super::STATE;
+
+const MAX_CYCLES_RECORDS: usize = 100;
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct CyclesRecord {
+    pub checked_at: u64,
+    /// Balance of the instance before the top up
+    pub balance: Nat,
+    /// Cycles, which were burnt since the previous check
+    pub consumed: Nat,
+    pub topped_up: Nat,
+}
+
+/// chain id => the latest cycles checks of the instance, the oldest first
+pub struct CyclesHistory(StableBTreeMap<u32, Cbor<Vec<CyclesRecord>>, VMemory>);
+
+impl Default for CyclesHistory {
+    fn default() -> Self {
+        Self(StableBTreeMap::init(
+            crate::memory::get_cycles_history_memory(),
+        ))
+    }
+}
+
+impl CyclesHistory {
+    pub fn add(chain_id: u32, checked_at: u64, balance: Nat, topped_up: Nat) {
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            let inner = &mut state.cycles_history.0;
+
+            let mut records = inner.get(&chain_id).map(|r| r.0).unwrap_or_default();
+
+            let consumed = records
+                .last()
+                .map(|previous| consumed(previous, &balance))
+                .unwrap_or_default();
+
+            records.push(CyclesRecord {
+                checked_at,
+                balance,
+                consumed,
+                topped_up,
+            });
+
+            if records.len() > MAX_CYCLES_RECORDS {
+                records.drain(..records.len() - MAX_CYCLES_RECORDS);
+            }
+
+            inner.insert(chain_id, Cbor(records));
+        });
+    }
+
+    pub fn get(chain_id: u32) -> Vec<CyclesRecord> {
+        STATE.with(|state| {
+            state
+                .borrow()
+                .cycles_history
+                .0
+                .get(&chain_id)
+                .map(|records| records.0)
+                .unwrap_or_default()
+        })
+    }
+
+    pub fn remove(chain_id: u32) {
+        STATE.with(|state| state.borrow_mut().cycles_history.0.remove(&chain_id));
+    }
+}
+
+/// Cycles, which were burnt since the previous check, cycles sent by others are not counted
+fn consumed(previous: &CyclesRecord, balance: &Nat) -> Nat {
+    let available = previous.balance.clone() + previous.topped_up.clone();
+
+    if &available > balance {
+        available - balance.clone()
+    } else {
+        Nat::from(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consumed() {
+        let previous = CyclesRecord {
+            checked_at: 0,
+            balance: Nat::from(1_000u64),
+            consumed: Nat::from(0u64),
+            topped_up: Nat::from(500u64),
+        };
+
+        assert_eq!(consumed(&previous, &Nat::from(1_200u64)), Nat::from(300u64));
+        assert_eq!(consumed(&previous, &Nat::from(2_000u64)), Nat::from(0u64));
+    }
+}
@@ -7,20 +7,56 @@ use serde::{Deserialize, Serialize};
 
 use crate::memory::VMemory;
 
-use self::apollo_instance::ApolloInstance;
+use self::{
+    apollo_instance::ApolloInstance,
+    cycles::CyclesHistory,
+    wasm::{InstanceWasms, WasmVersions},
+};
 
 pub mod apollo_instance;
 pub mod custom_return_types;
+pub mod cycles;
+pub mod wasm;
 
-#[derive(Serialize, Deserialize, Debug, Default, CandidType, Clone)]
+const DEFAULT_CYCLES_CHECK_INTERVAL_SEC: u64 = 60 * 60;
+
+#[derive(Serialize, Deserialize, Debug, CandidType, Clone)]
 pub struct Metadata {
     pub key_name: String,
     pub sybil_canister_address: String,
+    // instances are topped up with `cycles_top_up_amount`, when their balance is below the threshold.
+    // Both are 0 by default, so the balances are only recorded until the top-ups are configured
+    #[serde(default)]
+    pub cycles_top_up_threshold: u128,
+    #[serde(default)]
+    pub cycles_top_up_amount: u128,
+    // an hour by default, 0 disables the cycles monitoring of the instances
+    #[serde(default = "default_cycles_check_interval_sec")]
+    pub cycles_check_interval_sec: u64,
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Self {
+            key_name: String::new(),
+            sybil_canister_address: String::new(),
+            cycles_top_up_threshold: 0,
+            cycles_top_up_amount: 0,
+            cycles_check_interval_sec: DEFAULT_CYCLES_CHECK_INTERVAL_SEC,
+        }
+    }
+}
+
+fn default_cycles_check_interval_sec() -> u64 {
+    DEFAULT_CYCLES_CHECK_INTERVAL_SEC
 }
 
 #[derive(Serialize, Deserialize, CandidType, Clone)]
 pub struct UpdateMetadata {
     sybil_canister_address: Option<String>,
+    cycles_top_up_threshold: Option<u128>,
+    cycles_top_up_amount: Option<u128>,
+    cycles_check_interval_sec: Option<u64>,
 }
 
 impl Metadata {
@@ -28,6 +64,15 @@ impl Metadata {
         if let Some(sybil_canister_address) = update.sybil_canister_address {
             self.sybil_canister_address = sybil_canister_address;
         }
+        if let Some(cycles_top_up_threshold) = update.cycles_top_up_threshold {
+            self.cycles_top_up_threshold = cycles_top_up_threshold;
+        }
+        if let Some(cycles_top_up_amount) = update.cycles_top_up_amount {
+            self.cycles_top_up_amount = cycles_top_up_amount;
+        }
+        if let Some(cycles_check_interval_sec) = update.cycles_check_interval_sec {
+            self.cycles_check_interval_sec = cycles_check_interval_sec;
+        }
     }
 }
 
@@ -39,6 +84,18 @@ pub struct State {
     #[serde(skip, default = "init_chains")]
     // TODO: change to u64
     pub chains: StableBTreeMap<u32, Cbor<ApolloInstance>, VMemory>,
+
+    #[serde(skip)]
+    pub cycles_history: CyclesHistory,
+
+    #[serde(skip)]
+    pub instance_wasms: InstanceWasms,
+
+    #[serde(skip)]
+    pub wasm_versions: WasmVersions,
+    // the module, which is being uploaded, an unfinished upload doesn't survive the upgrade
+    #[serde(skip)]
+    pub instance_wasm_upload: Vec<u8>,
 }
 
 thread_local! {
@@ -59,6 +116,10 @@ impl Default for State {
         Self {
             metadata: init_metadata(),
             chains: init_chains(),
+            cycles_history: CyclesHistory::default(),
+            instance_wasms: InstanceWasms::default(),
+            wasm_versions: WasmVersions::default(),
+            instance_wasm_upload: vec![],
         }
     }
 }
@@ -0,0 +1,348 @@
+use apollo_utils::{errors::ApolloError, memory::Cbor};
+use candid::{CandidType, Nat};
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::memory::VMemory;
+
+use super::STATE;
+
+const WASM_MAGIC: &[u8] = b"\0asm";
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InstanceWasm {
+    pub version: u32,
+    /// Hex encoded sha256 of the module
+    pub hash: String,
+    pub created_at: u64,
+    #[serde(with = "serde_bytes")]
+    pub module: Vec<u8>,
+}
+
+/// Stored instance wasm without the module itself
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct WasmVersion {
+    pub version: u32,
+    pub hash: String,
+    pub created_at: u64,
+    pub size: u64,
+}
+
+impl From<&InstanceWasm> for WasmVersion {
+    fn from(wasm: &InstanceWasm) -> Self {
+        Self {
+            version: wasm.version,
+            hash: wasm.hash.clone(),
+            created_at: wasm.created_at,
+            size: wasm.module.len() as u64,
+        }
+    }
+}
+
+/// Chains, which are upgraded
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub enum UpgradeRollout {
+    /// Every chain, a failed chain doesn't stop the others
+    All,
+    Chains(Vec<Nat>),
+    /// The canary chain is upgraded first, the other chains are upgraded only if it succeeded
+    Canary(Nat),
+}
+
+/// version => instance wasm, versions are incremented from 1
+pub struct InstanceWasms(StableBTreeMap<u32, Cbor<InstanceWasm>, VMemory>);
+
+impl Default for InstanceWasms {
+    fn default() -> Self {
+        Self(StableBTreeMap::init(
+            crate::memory::get_instance_wasms_memory(),
+        ))
+    }
+}
+
+/// version => metadata of the instance wasm
+pub struct WasmVersions(StableBTreeMap<u32, Cbor<WasmVersion>, VMemory>);
+
+impl Default for WasmVersions {
+    fn default() -> Self {
+        Self(StableBTreeMap::init(
+            crate::memory::get_wasm_versions_memory(),
+        ))
+    }
+}
+
+impl InstanceWasms {
+    /// Stores the module as a new version, the latest version is returned if its hash is the same
+    pub fn add(module: Vec<u8>, created_at: u64) -> u32 {
+        let hash = hash(&module);
+
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+
+            let latest = state.wasm_versions.0.last_key_value();
+            if let Some((version, wasm_version)) = &latest {
+                if wasm_version.hash == hash {
+                    return *version;
+                }
+            }
+
+            let version = latest.map_or(1, |(version, _)| version + 1);
+            let wasm = InstanceWasm {
+                version,
+                hash,
+                created_at,
+                module,
+            };
+
+            state
+                .wasm_versions
+                .0
+                .insert(version, Cbor(WasmVersion::from(&wasm)));
+            state.instance_wasms.0.insert(version, Cbor(wasm));
+
+            version
+        })
+    }
+
+    /// Appends the chunk to the module being uploaded, a chunk at offset 0 restarts the upload
+    pub fn add_chunk(chunk: &[u8], offset: u64) -> Result<(), ApolloError> {
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            let upload = &mut state.instance_wasm_upload;
+
+            if offset == 0 {
+                upload.clear();
+            }
+
+            if upload.len() as u64 != offset {
+                return Err(ApolloError::InvalidWasmChunkOffset(
+                    upload.len() as u64,
+                    offset,
+                ));
+            }
+
+            upload.extend_from_slice(chunk);
+
+            Ok(())
+        })
+    }
+
+    /// Takes the uploaded module, the next upload starts from scratch
+    pub fn take_upload() -> Vec<u8> {
+        STATE.with(|state| std::mem::take(&mut state.borrow_mut().instance_wasm_upload))
+    }
+
+    pub fn latest_version() -> Option<u32> {
+        STATE.with(|state| {
+            state
+                .borrow()
+                .wasm_versions
+                .0
+                .last_key_value()
+                .map(|(version, _)| version)
+        })
+    }
+
+    pub fn get(version: u32) -> Option<InstanceWasm> {
+        STATE.with(|state| {
+            state
+                .borrow()
+                .instance_wasms
+                .0
+                .get(&version)
+                .map(|wasm| wasm.0)
+        })
+    }
+
+    pub fn get_versions() -> Vec<WasmVersion> {
+        STATE.with(|state| {
+            state
+                .borrow()
+                .wasm_versions
+                .0
+                .iter()
+                .map(|(_, wasm_version)| wasm_version.0)
+                .collect()
+        })
+    }
+
+    /// Removes the version, which is not installed and is not the rollback target of any chain.
+    /// The latest version is kept, so the versions are not reused
+    pub fn remove(version: u32) -> Result<(), ApolloError> {
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+
+            if !state.wasm_versions.0.contains_key(&version) {
+                return Err(ApolloError::WasmVersionNotFound(version));
+            }
+
+            if state
+                .wasm_versions
+                .0
+                .last_key_value()
+                .map(|(latest, _)| latest)
+                == Some(version)
+            {
+                return Err(ApolloError::LatestWasmVersion(version));
+            }
+
+            let installed_on = state.chains.iter().find(|(_, instance)| {
+                instance.0.wasm_version == Some(version)
+                    || instance.0.previous_wasm_version == Some(version)
+            });
+            if let Some((chain_id, _)) = installed_on {
+                return Err(ApolloError::WasmVersionInstalled(
+                    version,
+                    Nat::from(chain_id),
+                ));
+            }
+
+            state.wasm_versions.0.remove(&version);
+            state.instance_wasms.0.remove(&version);
+
+            Ok(())
+        })
+    }
+}
+
+impl WasmVersions {
+    /// Stores the metadata of the wasms, which were uploaded before it was stored separately
+    pub fn migrate() {
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            if !state.wasm_versions.0.is_empty() {
+                return;
+            }
+
+            let wasm_versions: Vec<_> = state
+                .instance_wasms
+                .0
+                .iter()
+                .map(|(version, wasm)| (version, WasmVersion::from(&wasm.0)))
+                .collect();
+
+            for (version, wasm_version) in wasm_versions {
+                state.wasm_versions.0.insert(version, Cbor(wasm_version));
+            }
+        })
+    }
+}
+
+/// Whether the module is a wasm binary or a gzipped one
+pub fn is_wasm(module: &[u8]) -> bool {
+    module.starts_with(WASM_MAGIC) || module.starts_with(GZIP_MAGIC)
+}
+
+fn hash(module: &[u8]) -> String {
+    hex::encode(Sha256::digest(module))
+}
+
+#[cfg(test)]
+mod tests {
+    use candid::Principal;
+
+    use super::*;
+    use crate::types::apollo_instance::ApolloInstance;
+
+    #[test]
+    fn test_add_chunk() {
+        assert!(InstanceWasms::add_chunk(b"\0as", 0).is_ok());
+        assert!(InstanceWasms::add_chunk(b"m", 3).is_ok());
+        assert!(matches!(
+            InstanceWasms::add_chunk(b"\x01", 2),
+            Err(ApolloError::InvalidWasmChunkOffset(4, 2))
+        ));
+        assert!(matches!(
+            InstanceWasms::add_chunk(b"\x01", 5),
+            Err(ApolloError::InvalidWasmChunkOffset(4, 5))
+        ));
+
+        // the upload is restarted at offset 0
+        assert!(InstanceWasms::add_chunk(b"\0asm", 0).is_ok());
+        assert!(InstanceWasms::add_chunk(b"\x01", 4).is_ok());
+        assert_eq!(InstanceWasms::take_upload(), b"\0asm\x01");
+        assert!(InstanceWasms::take_upload().is_empty());
+    }
+
+    #[test]
+    fn test_add() {
+        assert_eq!(InstanceWasms::latest_version(), None);
+
+        assert_eq!(InstanceWasms::add(b"\0asm\x01".to_vec(), 1), 1);
+        // the same module as the latest one isn't stored again
+        assert_eq!(InstanceWasms::add(b"\0asm\x01".to_vec(), 2), 1);
+        assert_eq!(InstanceWasms::add(b"\0asm\x02".to_vec(), 3), 2);
+        // only the latest version is compared
+        assert_eq!(InstanceWasms::add(b"\0asm\x01".to_vec(), 4), 3);
+
+        let versions = InstanceWasms::get_versions();
+        assert_eq!(
+            versions
+                .iter()
+                .map(|version| (version.version, version.created_at, version.size))
+                .collect::<Vec<_>>(),
+            vec![(1, 1, 5), (2, 3, 5), (3, 4, 5)]
+        );
+        assert_eq!(versions[0].hash, versions[2].hash);
+        assert_eq!(InstanceWasms::latest_version(), Some(3));
+        assert_eq!(InstanceWasms::get(2).unwrap().module, b"\0asm\x02");
+    }
+
+    #[test]
+    fn test_remove() {
+        for module in [b"\0asm\x01", b"\0asm\x02", b"\0asm\x03", b"\0asm\x04"] {
+            InstanceWasms::add(module.to_vec(), 0);
+        }
+
+        STATE.with(|state| {
+            state.borrow_mut().chains.insert(
+                1,
+                Cbor(ApolloInstance {
+                    canister_id: Principal::anonymous(),
+                    chain_id: Nat::from(1),
+                    apollo_main_address: String::new(),
+                    is_active: true,
+                    wasm_version: Some(3),
+                    previous_wasm_version: Some(2),
+                }),
+            )
+        });
+
+        assert!(matches!(
+            InstanceWasms::remove(2),
+            Err(ApolloError::WasmVersionInstalled(2, _))
+        ));
+        assert!(matches!(
+            InstanceWasms::remove(3),
+            Err(ApolloError::WasmVersionInstalled(3, _))
+        ));
+        assert!(matches!(
+            InstanceWasms::remove(4),
+            Err(ApolloError::LatestWasmVersion(4))
+        ));
+        assert!(matches!(
+            InstanceWasms::remove(5),
+            Err(ApolloError::WasmVersionNotFound(5))
+        ));
+
+        assert!(InstanceWasms::remove(1).is_ok());
+        assert!(InstanceWasms::get(1).is_none());
+        assert_eq!(
+            InstanceWasms::get_versions()
+                .iter()
+                .map(|version| version.version)
+                .collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn test_is_wasm() {
+        assert!(is_wasm(b"\0asm\x01\0\0\0"));
+        assert!(is_wasm(&[0x1f, 0x8b, 0x08, 0x00]));
+        assert!(!is_wasm(b"asm"));
+        assert!(!is_wasm(&[]));
+    }
+}
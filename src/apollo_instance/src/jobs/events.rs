@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use apollo_utils::apollo_instance::CoordinatorAbiVersion;
+use ic_web3_rs::{
+    ethabi::{self, Event, Log, RawLog},
+    types::H256,
+};
+use thiserror::Error;
+
+use crate::types::ApolloCoordinatorRequest;
+
+const APOLLO_COORDINATOR_ABI: &[u8] =
+    include_bytes!("../../../../assets/ApolloCoordinatorABI.json");
+
+/// Coordinator event, which is turned into a request for the AMA
+struct CoordinatorEvent {
+    /// Name of the event in the Apollo Coordinator ABI
+    name: &'static str,
+    decode: fn(Log) -> ApolloCoordinatorRequest,
+}
+
+/// To support a new coordinator event, add it to the ABI, register it here
+/// and handle the produced request in `process_requests`
+const COORDINATOR_EVENTS: &[CoordinatorEvent] = &[
+    CoordinatorEvent {
+        name: "DataFeedRequested",
+        decode: ApolloCoordinatorRequest::new_from_data_feed_log,
+    },
+    CoordinatorEvent {
+        name: "RandomFeedRequested",
+        decode: ApolloCoordinatorRequest::new_from_random_feed_log,
+    },
+    CoordinatorEvent {
+        name: "MultiFeedRequested",
+        decode: ApolloCoordinatorRequest::new_from_multi_feed_log,
+    },
+    CoordinatorEvent {
+        name: "CrossChainDataFeedRequested",
+        decode: ApolloCoordinatorRequest::new_from_cross_chain_data_feed_log,
+    },
+];
+
+/// Reason, why the log was not turned into a request
+#[derive(Error, Debug)]
+pub enum SkipReason {
+    #[error("Unknown topic")]
+    UnknownTopic,
+    #[error("Invalid log: {0}")]
+    InvalidLog(String),
+}
+
+/// Registered coordinator events by their topic
+pub struct EventRegistry(HashMap<H256, (Event, fn(Log) -> ApolloCoordinatorRequest)>);
+
+impl Default for EventRegistry {
+    fn default() -> Self {
+        Self::new(CoordinatorAbiVersion::V1)
+    }
+}
+
+impl EventRegistry {
+    /// Registry for the events of the given coordinator ABI version
+    pub fn new(abi_version: CoordinatorAbiVersion) -> Self {
+        let abi = match abi_version {
+            CoordinatorAbiVersion::V1 => APOLLO_COORDINATOR_ABI,
+        };
+
+        let abi = ethabi::Contract::load(abi).expect("should be able to load the coordinator abi");
+
+        Self(
+            COORDINATOR_EVENTS
+                .iter()
+                .map(|event| {
+                    let abi_event = abi
+                        .event(event.name)
+                        .expect("should be able to get event by name")
+                        .clone();
+
+                    (abi_event.signature(), (abi_event, event.decode))
+                })
+                .collect(),
+        )
+    }
+
+    pub fn decode(&self, raw_log: RawLog) -> Result<ApolloCoordinatorRequest, SkipReason> {
+        let (event, decode) = raw_log
+            .topics
+            .first()
+            .and_then(|topic| self.0.get(topic))
+            .ok_or(SkipReason::UnknownTopic)?;
+
+        let log = event
+            .parse_log(raw_log)
+            .map_err(|err| SkipReason::InvalidLog(err.to_string()))?;
+
+        Ok(decode(log))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use ic_web3_rs::{ethabi::Token, types::H160};
+
+    use super::*;
+
+    #[test]
+    fn test_registry_topics() {
+        let registry = EventRegistry::default();
+
+        assert_eq!(registry.0.len(), COORDINATOR_EVENTS.len());
+        assert!(registry.0.contains_key(
+            &H256::from_str("0x23752ae5400f82f705b104bd992d5ae9631719e025bb5934d3ed82d5aa9c27ee")
+                .unwrap()
+        ));
+        assert!(registry.0.contains_key(
+            &H256::from_str("0x266a11fde650ce93d717e570d9ebbfa8746356fe5c7c73647a03d56dde7027c0")
+                .unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_unknown_topic() {
+        let registry = EventRegistry::default();
+
+        let raw_log = RawLog {
+            topics: vec![H256::zero()],
+            data: vec![],
+        };
+
+        assert!(matches!(
+            registry.decode(raw_log),
+            Err(SkipReason::UnknownTopic)
+        ));
+    }
+
+    #[test]
+    fn test_decode_multi_feed() {
+        let registry = EventRegistry::default();
+        let (topic, _) = registry
+            .0
+            .iter()
+            .find(|(_, (event, _))| event.name == "MultiFeedRequested")
+            .unwrap();
+
+        let requester = H160::repeat_byte(1);
+        let raw_log = RawLog {
+            topics: vec![*topic, H256::from_low_u64_be(7), requester.into()],
+            data: ethabi::encode(&[
+                Token::Array(vec![
+                    Token::String("ETH/USD".to_string()),
+                    Token::String("BTC/USD".to_string()),
+                ]),
+                Token::Uint(100_000.into()),
+            ]),
+        };
+
+        let ApolloCoordinatorRequest::MultiFeed {
+            request_id,
+            feed_ids,
+            callback_gas_limit,
+            requester: decoded_requester,
+        } = registry.decode(raw_log).unwrap()
+        else {
+            panic!("should be a multi feed request");
+        };
+
+        assert_eq!(request_id, 7.into());
+        assert_eq!(feed_ids, vec!["ETH/USD", "BTC/USD"]);
+        assert_eq!(callback_gas_limit, 100_000.into());
+        assert_eq!(decoded_requester, requester);
+    }
+}
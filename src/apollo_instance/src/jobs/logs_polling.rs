@@ -1,63 +1,89 @@
-use std::str::FromStr;
-
 use apollo_utils::{
     address,
     errors::LogsPoolingError,
-    get_metadata, get_state, log, update_state,
+    get_metadata, log, time,
     web3::{self, Web3Instance},
 };
-use ic_web3_rs::{
-    ethabi::{self, RawLog},
-    types::{H256, U256},
-    Transport,
-};
+use ic_web3_rs::{ethabi::RawLog, Transport};
 
-use crate::types::ApolloCoordinatorRequest;
-
-use super::process_requests;
+use crate::types::{
+    coordinators::{Coordinator, Coordinators},
+    subscriptions::Subscriptions,
+    unknown_events::UnknownEvents,
+    ApolloCoordinatorRequest, STATE,
+};
 
-const APOLLO_COORDINATOR_ABI: &[u8] =
-    include_bytes!("../../../../assets/ApolloCoordinatorABI.json");
-const APOOLLO_COORDINATOR_DATA_FEED_REQUESTED_EVENT_NAME: &str = "DataFeedRequested";
-const APOOLLO_COORDINATOR_RANDOM_FEED_REQUESTED_EVENT_NAME: &str = "RandomFeedRequested";
-const DATA_FEED_REQUESTED_TOPIC: &str =
-    "0x23752ae5400f82f705b104bd992d5ae9631719e025bb5934d3ed82d5aa9c27ee";
-const RANDOM_FEED_REQUESTED_TOPIC: &str =
-    "0x266a11fde650ce93d717e570d9ebbfa8746356fe5c7c73647a03d56dde7027c0";
+use super::{events::EventRegistry, gas_price, process_requests};
 
 pub async fn _execute() -> Result<(), LogsPoolingError> {
     let w3 = web3::instance(get_metadata!(chain_rpc), get_metadata!(evm_rpc_canister))?;
 
-    let (requests, last_block) = get_requests(&w3).await?;
+    let mut requests = vec![];
+    let mut last_blocks = vec![];
+
+    for coordinator in Coordinators::get_all() {
+        let (coordinator_requests, last_block) = get_requests(&w3, &coordinator).await?;
+
+        requests.extend(coordinator_requests);
+        last_blocks.push((coordinator.address, last_block));
+    }
+
+    for subscription in Subscriptions::get_to_check(time::in_seconds()) {
+        match subscription.to_request() {
+            Ok(request) => requests.push(request),
+            Err(err) => log!(
+                "[EXECUTION] subscription: {}, unable to create request: {}",
+                subscription.id,
+                err
+            ),
+        }
+    }
+
+    // relayed requests are removed only once they are processed, new ones are added to the end meanwhile
+    let relayed_requests = STATE.with(|state| state.borrow().relayed_requests.clone());
+    let relayed_count = relayed_requests.len();
+
+    for relayed_request in relayed_requests {
+        match ApolloCoordinatorRequest::from_relayed_request(relayed_request) {
+            Ok(request) => requests.push(request),
+            Err(err) => log!("[EXECUTION] unable to create relayed request: {}", err),
+        }
+    }
 
-    // multiply the gas_price to 1.2 to avoid long transaction confirmation
-    let gas_price: U256 = (w3.get_gas_price().await? * 12) / 10;
+    let gas_price = gas_price(&w3).await?;
 
     process_requests(&w3, requests, gas_price)
         .await
         .map_err(|err| LogsPoolingError::FailedToProcessRequests(err.to_string()))?;
 
-    update_state!(last_parsed_logs_from_block, Some(last_block));
+    STATE.with(|state| {
+        state.borrow_mut().relayed_requests.drain(..relayed_count);
+    });
+
+    for (address, last_block) in last_blocks {
+        Coordinators::set_last_parsed_block(Some(&address), Some(last_block));
+    }
 
     Ok(())
 }
 
-/// Returns the requests for the Apollo Coordinator contract
+/// Returns the requests for the coordinator contract
 /// alongside the last block number from which the logs were parsed
 async fn get_requests<T: Transport>(
     w3: &Web3Instance<T>,
+    coordinator: &Coordinator,
 ) -> Result<(Vec<ApolloCoordinatorRequest>, u64), LogsPoolingError> {
-    let last_parsed_logs_from_block =
-        if let Some(last_parsed) = get_state!(last_parsed_logs_from_block) {
-            last_parsed + 1
-        } else {
-            let last_block = w3.get_block_number().await?;
-            update_state!(last_parsed_logs_from_block, Some(last_block));
-            last_block
-        };
+    let last_parsed_logs_from_block = if let Some(last_parsed) = coordinator.last_parsed_block {
+        last_parsed + 1
+    } else {
+        let last_block = w3.get_block_number().await?;
+        Coordinators::set_last_parsed_block(Some(&coordinator.address), Some(last_block));
+        last_block
+    };
 
     log!(
-        "[EXECUTION] Getting logs from block {} to the latest block",
+        "[EXECUTION] Getting logs of {} from block {} to the latest block",
+        coordinator.address,
         last_parsed_logs_from_block
     );
 
@@ -65,11 +91,9 @@ async fn get_requests<T: Transport>(
         .get_logs(
             last_parsed_logs_from_block,
             None,
-            Some(vec![
-                H256::from_str(DATA_FEED_REQUESTED_TOPIC).expect("should be able to parse"),
-                H256::from_str(RANDOM_FEED_REQUESTED_TOPIC).expect("should be able to parse"),
-            ]),
-            Some(address::to_h160(&get_metadata!(apollo_coordinator))?),
+            // all the coordinator events are fetched, so the unknown ones are recorded
+            None,
+            Some(address::to_h160(&coordinator.address)?),
         )
         .await;
 
@@ -87,15 +111,7 @@ async fn get_requests<T: Transport>(
         }
     };
 
-    let apollo_coordinator_abi = ethabi::Contract::load(APOLLO_COORDINATOR_ABI).unwrap();
-
-    let data_feed_event = apollo_coordinator_abi
-        .event(APOOLLO_COORDINATOR_DATA_FEED_REQUESTED_EVENT_NAME)
-        .expect("should be able to get event by name");
-    let random_feed_event = apollo_coordinator_abi
-        .event(APOOLLO_COORDINATOR_RANDOM_FEED_REQUESTED_EVENT_NAME)
-        .expect("should be able to get event by name");
-
+    let registry = EventRegistry::new(coordinator.abi_version);
     let mut requests = Vec::with_capacity(logs.len());
 
     if logs.is_empty() {
@@ -110,39 +126,23 @@ async fn get_requests<T: Transport>(
         .as_u64();
 
     for log in logs {
+        let block = log.block_number.unwrap_or_default().as_u64();
+        let tx_hash = format!("{:?}", log.transaction_hash.unwrap_or_default());
+        let topic = log
+            .topics
+            .first()
+            .map(|topic| format!("{:?}", topic))
+            .unwrap_or_default();
+
         let raw_log = RawLog {
             topics: log.topics,
             data: log.data.0,
         };
 
-        let first_topic = format!(
-            "{:?}",
-            raw_log
-                .topics
-                .first()
-                .expect("should be able to get first topic")
-        );
-
-        log!("First topic: {:?}", first_topic);
-
-        match first_topic.as_str() {
-            DATA_FEED_REQUESTED_TOPIC => {
-                let parsed_log = data_feed_event
-                    .parse_log(raw_log)
-                    .map_err(|err| LogsPoolingError::AbiParsingError(err.to_string()))?;
-
-                requests.push(ApolloCoordinatorRequest::new_from_data_feed_log(parsed_log))
-            }
-            RANDOM_FEED_REQUESTED_TOPIC => {
-                let parsed_log = random_feed_event
-                    .parse_log(raw_log)
-                    .map_err(|err| LogsPoolingError::AbiParsingError(err.to_string()))?;
-
-                requests.push(ApolloCoordinatorRequest::new_from_random_feed_log(
-                    parsed_log,
-                ))
-            }
-            _ => unreachable!("should be able to parse"),
+        match registry.decode(raw_log) {
+            Ok(request) => requests.push(request),
+            // skipped events are recorded, so they don't block the polling
+            Err(reason) => UnknownEvents::add(topic, reason.to_string(), block, tx_hash),
         }
     }
 
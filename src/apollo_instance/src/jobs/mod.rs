@@ -1,29 +1,58 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeSet, HashMap, HashSet},
+    time::Duration,
+};
+
 use apollo_utils::{
     address,
-    errors::MulticallError,
+    apollo_instance::{FeeConversionRate, FeeDenomination},
+    data_source::DataSource,
+    errors::{DataSourceError, Web3Error},
     get_metadata, log,
     multicall::{self, Call},
     nat::{ToNatType, ToNativeTypes},
-    sybil::get_sybil_feed,
-    web3::Web3Instance,
+    sybil::{AssetDataResult, SybilAssetData},
+    time,
+    web3::{Web3Instance, ECDSA_SIGN_CYCLES},
+};
+use candid::Nat;
+use futures::future::join_all;
+use ic_cdk::api::canister_balance128;
+use ic_web3_rs::{
+    types::{TransactionReceipt, H160, U256},
+    Transport,
 };
-use ic_web3_rs::ethabi::Function;
-use ic_web3_rs::{ethabi::Token, types::U256, Transport};
 
 use crate::{
     types::{
-        allowances::Allowances, asset_data::AssetData, balances::Balances, timer::Timer,
-        ApolloCoordinatorRequest,
+        allowances::Allowances,
+        asset_data::AssetData,
+        balances::Balances,
+        callback_layouts::CallbackLayouts,
+        callback_results::{CallbackResult, CallbackResults},
+        nonce_manager::{NonceManager, PendingCall, TxPurpose},
+        request_volumes::RequestVolumes,
+        stats::Stats,
+        subscriptions::{Subscription, Subscriptions},
+        timer::Timer,
+        ApolloCoordinatorRequest, STATE,
     },
     utils::apollo_evm_address,
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
+mod events;
 mod logs_polling;
+mod nonce_manager;
+mod relay;
+mod solvency;
 pub mod withdraw;
 
-const TARGET_FUNCTION_ABI: &str = include_str!("../../../../assets/TargetFunctionABI.json");
+const MAX_STALE_DATA_ATTEMPTS: u32 = 3;
+// the source needs time to get a new price, the delay is doubled after every attempt
+const STALE_DATA_RETRY_DELAY: Duration = Duration::from_secs(5);
 
 pub fn execute() {
     if !Timer::is_active() {
@@ -33,15 +62,31 @@ pub fn execute() {
     log!("---Execution started---");
 
     ic_cdk::spawn(async {
-        if let Err(e) = logs_polling::_execute().await {
+        let started_at = time::in_seconds();
+        let cycles_before = canister_balance128();
+
+        nonce_manager::execute().await;
+
+        // requests stay on-chain and are fulfilled once the AMA is solvent again
+        if !solvency::check().await {
+            log!("[EXECUTION] AMA is insolvent or was never checked, fulfillment is paused");
+        } else if let Err(e) = logs_polling::_execute().await {
             log!("Error while executing publisher job: {e:?}");
         } else {
             log!("Publisher job executed successfully");
         }
 
-        Timer::set_timer(execute);
+        // withdrawals are executed after the requests processing,
+        // so the nonces of the AMA transactions don't collide
+        withdraw::withdraw().await;
 
-        withdraw::execute();
+        // cycles, which were received during the tick, are not counted
+        Stats::add_tick(
+            started_at,
+            cycles_before.saturating_sub(canister_balance128()),
+        );
+
+        Timer::set_timer(execute);
     });
 }
 
@@ -57,47 +102,100 @@ async fn process_requests<T: Transport>(
 
     log!("[EXECUTION] Processing {} requests", requests.len());
 
-    let mut calls = Vec::with_capacity(requests.len());
+    let cycles_at_start = canister_balance128();
+
+    // requests are processed on the next tick, if the fee can't be converted
+    let fee_rate = fee_conversion_rate().await?;
+
+    let mut funded_requests = Vec::with_capacity(requests.len());
 
     for apollo_coordinator_request in requests {
+        // the requester was charged on the source chain
+        if let ApolloCoordinatorRequest::RelayedDataFeed { .. } = apollo_coordinator_request {
+            funded_requests.push(apollo_coordinator_request);
+            continue;
+        }
+
         let requester = apollo_coordinator_request.requester();
         let callback_gas_limit = apollo_coordinator_request.callback_gas_limit();
-        let feed_id = apollo_coordinator_request.feed_id();
+        let fee = request_fee(&apollo_coordinator_request, fee_rate.as_ref());
         let balance = Balances::get(&Allowances::get_allowed_user(address::from_h160(
             &requester,
         ))?)?
         .amount;
 
-        if balance
-            < get_metadata!(min_balance) + callback_gas_limit.to_nat() + get_metadata!(apollos_fee)
-        {
+        if balance < get_metadata!(min_balance) + callback_gas_limit.to_nat() + fee.clone() {
             log!(
-                "[EXECUTION] chain: {}, not enough balance for requester {}. Needed (min_balance + callback_gas_limit + apollos_fee): {} + {} + {} = {}, current: {}",
+                "[EXECUTION] chain: {}, not enough balance for requester {}. Needed (min_balance + callback_gas_limit + fee): {} + {} + {} = {}, current: {}",
                 get_metadata!(chain_id),
                 requester,
                 get_metadata!(min_balance),
                 callback_gas_limit,
-                get_metadata!(apollos_fee),
-                get_metadata!(min_balance) + callback_gas_limit.to_nat() + get_metadata!(apollos_fee),
+                fee,
+                get_metadata!(min_balance) + callback_gas_limit.to_nat() + fee.clone(),
                 balance
             );
 
             continue;
         }
 
+        funded_requests.push(apollo_coordinator_request);
+    }
+
+    let (cross_chain_requests, funded_requests): (Vec<_>, Vec<_>) =
+        funded_requests.into_iter().partition(|request| {
+            matches!(request, ApolloCoordinatorRequest::CrossChainDataFeed { .. })
+        });
+
+    relay::relay_requests(cross_chain_requests, fee_rate.as_ref()).await;
+
+    let feeds = fetch_data_feeds(&funded_requests).await;
+    let feed_cycles = feed_outcall_cycles(&funded_requests);
+
+    let mut calls = Vec::with_capacity(funded_requests.len());
+    // request id => delivered value of the subscription, it is marked as delivered once the tx is mined
+    let mut subscription_values = HashMap::new();
+    // (requester, request id, reserved amount) of the calls, which were paid upfront
+    let mut reserved_calls = vec![];
+
+    for apollo_coordinator_request in funded_requests {
+        let requester = apollo_coordinator_request.requester();
+        let request_id = apollo_coordinator_request.request_id();
+        let callback_gas_limit = apollo_coordinator_request.callback_gas_limit();
+        let feed_id = apollo_coordinator_request.feed_id();
+        let prepaid = matches!(
+            apollo_coordinator_request,
+            ApolloCoordinatorRequest::RelayedDataFeed { .. }
+        );
+        let fee = request_fee(&apollo_coordinator_request, fee_rate.as_ref());
+
+        // only the cycles, which are attached to the calls of the request, are billed
+        let mut billed_cycles: u128 = apollo_coordinator_request
+            .feed_ids()
+            .iter()
+            .map(|feed_id| feed_cycles[feed_id])
+            .sum();
+        if matches!(
+            apollo_coordinator_request,
+            ApolloCoordinatorRequest::RandomFeed { .. }
+        ) && get_metadata!(sign_randomness)
+        {
+            billed_cycles += ECDSA_SIGN_CYCLES as u128;
+        }
+
         let sybil_feed_result = match apollo_coordinator_request {
             ApolloCoordinatorRequest::DataFeed {
                 request_id,
                 feed_id,
                 ..
-            } => {
-                let sybil_asset_data =
-                    get_sybil_feed(get_metadata!(sybil_canister_address), feed_id).await;
-
-                sybil_asset_data.map(|data| {
-                    AssetData::from_sybil_asset_data_and_req_id(request_id.as_u64(), data)
-                })
             }
+            | ApolloCoordinatorRequest::RelayedDataFeed {
+                request_id,
+                feed_id,
+                ..
+            } => feeds[&feed_id]
+                .clone()
+                .map(|data| AssetData::from_sybil_asset_data_and_req_id(request_id.as_u64(), data)),
             ApolloCoordinatorRequest::RandomFeed {
                 request_id,
                 num_words,
@@ -109,10 +207,34 @@ async fn process_requests<T: Transport>(
                     num_words: num_words.as_u64(),
                 })
             }
+            ApolloCoordinatorRequest::MultiFeed {
+                request_id,
+                feed_ids,
+                ..
+            } => feed_ids
+                .iter()
+                .map(|feed_id| {
+                    feeds[feed_id].clone().map(|data| {
+                        AssetData::from_sybil_asset_data_and_req_id(request_id.as_u64(), data)
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(|feeds| AssetData::MultiFeed {
+                    request_id: request_id.as_u64(),
+                    feeds,
+                }),
+            // relayed to the target chain instance above
+            ApolloCoordinatorRequest::CrossChainDataFeed { .. } => continue,
         };
 
         let sybil_feed = match sybil_feed_result {
             Ok(feed) => feed,
+            Err(err @ (DataSourceError::StaleData(..) | DataSourceError::InvalidFeedId(_))) => {
+                // the request won't be seen again, so it is marked as failed
+                record_failed_request(request_id.as_u64(), &requester, &err.to_string());
+
+                continue;
+            }
             Err(err) => {
                 log!(
                     "[EXECUTION] chain: {}, requester: {}, feed_id: {}, error: {}",
@@ -126,73 +248,514 @@ async fn process_requests<T: Transport>(
             }
         };
 
-        let target_function = serde_json::from_str::<Function>(TARGET_FUNCTION_ABI)?;
+        let subscription_id = Subscription::id_from_request_id(request_id.as_u64());
+        if let Some(subscription_id) = subscription_id {
+            if !is_subscription_delivery_needed(subscription_id, &sybil_feed) {
+                continue;
+            }
+        }
 
-        let data = ic_web3_rs::ethabi::encode(&sybil_feed.encode().await);
+        // a bad layout of one requester doesn't stop the other requests
+        let layout = match CallbackLayouts::get(&address::from_h160(&requester)) {
+            Ok(layout) => layout,
+            Err(err) => {
+                log!(
+                    "[EXECUTION] chain: {}, requester: {}, unable to get callback layout: {}",
+                    get_metadata!(chain_id),
+                    requester,
+                    err
+                );
+
+                record_failed_request(request_id.as_u64(), &requester, &err.to_string());
+
+                continue;
+            }
+        };
+
+        // the spent cycles are reported, they include the messages, which run during the awaits
+        let cycles_before_encoding = canister_balance128();
+        let call_data = match sybil_feed.encode_call(&layout).await {
+            Ok(call_data) => call_data,
+            Err(err) => {
+                log!(
+                    "[EXECUTION] chain: {}, requester: {}, unable to encode callback: {}",
+                    get_metadata!(chain_id),
+                    requester,
+                    err
+                );
+
+                // the request won't be seen again, so it is marked as failed
+                record_failed_request(request_id.as_u64(), &requester, &err.to_string());
+
+                continue;
+            }
+        };
+
+        let cycles = cycles_before_encoding.saturating_sub(canister_balance128());
+        let surcharge = cycles_surcharge(billed_cycles);
 
-        let call_data = target_function
-            .encode_input(&[Token::Bytes(data)])
-            .map_err(|err| MulticallError::UnableToEncodeCallData(err.to_string()))?;
+        // the callback is paid upfront, so the balance can't be withdrawn or spent twice before the tx is mined.
+        // The batch signature share is unknown yet, so the whole signature is reserved
+        let reserved = if prepaid {
+            Nat::from(0)
+        } else {
+            gas_price.to_nat() * callback_gas_limit.to_nat()
+                + fee.clone()
+                + surcharge.clone()
+                + cycles_surcharge(ECDSA_SIGN_CYCLES as u128)
+        };
+
+        if !prepaid {
+            if let Err(err) = charge_requester(&address::from_h160(&requester), &reserved) {
+                log!(
+                    "[EXECUTION] chain: {}, requester: {}, unable to reserve {}: {}",
+                    get_metadata!(chain_id),
+                    requester,
+                    reserved,
+                    err
+                );
+
+                record_failed_request(request_id.as_u64(), &requester, &err.to_string());
+
+                continue;
+            }
+
+            reserved_calls.push((requester, request_id.as_u64(), reserved.clone()));
+        }
 
         calls.push(Call {
             target: requester,
             call_data,
             gas_limit: callback_gas_limit,
+            request_id: request_id.as_u64(),
+            prepaid,
+            fee: fee.to_u256(),
+            surcharge: surcharge.to_u256(),
+            reserved: reserved.to_u256(),
+            cycles,
         });
+
+        if subscription_id.is_some() {
+            subscription_values.insert(request_id.as_u64(), sybil_feed.value());
+        }
     }
 
-    let results = multicall::multicall(
-        w3,
-        &get_metadata!(multicall_address),
-        apollo_evm_address().await?,
-        calls.clone(),
-        get_metadata!(key_name),
-        get_metadata!(chain_id).to_u64(),
-        get_metadata!(block_gas_limit).to_u256(),
-        &gas_price,
-    )
-    .await?;
-
-    // TODO: reimplement
-    for (result, call) in results.iter().zip(calls) {
+    if calls.is_empty() {
+        return Ok(());
+    }
+
+    // (requester, request id) of the sent calls
+    let sent_calls = RefCell::new(HashSet::new());
+
+    let result: Result<()> = async {
+        let ama = apollo_evm_address().await?;
+        let nonce = NonceManager::next_nonce(w3.get_nonce(&ama).await?);
+
+        // fetching of the feeds, fee rate and nonce is shared by all the calls
+        let cycles_before_sending = canister_balance128();
+        let own_cycles: u128 = calls.iter().map(|call| call.cycles).sum();
+        let shared_cycles = cycles_at_start
+            .saturating_sub(cycles_before_sending)
+            .saturating_sub(own_cycles)
+            / calls.len() as u128;
+        let checkpoint = Cell::new(cycles_before_sending);
+
+        let rejected_calls = multicall::multicall(
+            w3,
+            &get_metadata!(multicall_address),
+            ama,
+            calls,
+            get_metadata!(key_name),
+            get_metadata!(chain_id).to_u64(),
+            get_metadata!(block_gas_limit).to_u256(),
+            &gas_price,
+            nonce,
+            |sent_tx, calls| {
+                sent_calls
+                    .borrow_mut()
+                    .extend(calls.iter().map(|call| (call.target, call.request_id)));
+
+                // signing and sending of the batch is shared by the calls of the batch
+                let balance = canister_balance128();
+                let batch_cycles = checkpoint.replace(balance).saturating_sub(balance)
+                    / calls.len().max(1) as u128;
+                // the surcharge is fixed at send time, so a later rate update doesn't change it
+                let sign_surcharge =
+                    cycles_surcharge(ECDSA_SIGN_CYCLES as u128 / calls.len().max(1) as u128)
+                        .to_u256();
+                let calls = calls
+                    .into_iter()
+                    .map(|call| Call {
+                        surcharge: call.surcharge + sign_surcharge,
+                        cycles: call.cycles + shared_cycles + batch_cycles,
+                        ..call
+                    })
+                    .collect::<Vec<_>>();
+
+                NonceManager::track(
+                    sent_tx,
+                    TxPurpose::multicall(&calls, gas_price, fee_rate.clone(), &subscription_values),
+                )
+            },
+        )
+        .await?;
+
+        // such requests can never be executed, their reservations are refunded below
+        for call in rejected_calls {
+            record_failed_request(
+                call.request_id,
+                &call.target,
+                "callback gas limit exceeds the block gas limit",
+            );
+        }
+
+        Ok(())
+    }
+    .await;
+
+    // the requests of the unsent calls, except the rejected ones, are processed again on the next tick
+    let sent_calls = sent_calls.into_inner();
+    for (requester, request_id, reserved) in reserved_calls {
+        if !sent_calls.contains(&(requester, request_id)) {
+            refund_reservation(&address::from_h160(&requester), &reserved);
+        }
+    }
+
+    result
+}
+
+/// Gas price of the AMA transactions, it is multiplied by 1.2 to avoid long transaction confirmation
+pub async fn gas_price<T: Transport>(w3: &Web3Instance<T>) -> Result<U256, Web3Error> {
+    Ok((w3.get_gas_price().await? * 12) / 10)
+}
+
+/// Subscriptions with a deviation threshold are checked on every tick,
+/// but delivered only once the heartbeat has elapsed or the value has deviated.
+/// A subscription isn't delivered again, while its previous delivery is not mined
+fn is_subscription_delivery_needed(subscription_id: u64, feed: &AssetData) -> bool {
+    match Subscriptions::get(subscription_id) {
+        Ok(subscription) => {
+            !NonceManager::has_pending_call(subscription.request_id())
+                && (subscription.is_due(time::in_seconds())
+                    || subscription.is_deviated(feed.value()))
+        }
+        // the subscription was removed while the feed was fetched
+        Err(_) => false,
+    }
+}
+
+/// Cycles of the feed outcalls per request, the outcall of the feed is shared by the requests of the tick
+fn feed_outcall_cycles(requests: &[ApolloCoordinatorRequest]) -> HashMap<String, u128> {
+    let mut requests_per_feed: HashMap<String, u128> = HashMap::new();
+    for feed_id in requests.iter().flat_map(ApolloCoordinatorRequest::feed_ids) {
+        *requests_per_feed.entry(feed_id).or_default() += 1;
+    }
+
+    let allowlist = get_metadata!(data_source_allowlist);
+
+    requests_per_feed
+        .into_iter()
+        .map(|(feed_id, requests)| {
+            let cycles = DataSource::from_feed_id(&feed_id, &allowlist)
+                .map(|source| source.outcall_cycles())
+                .unwrap_or_default();
+
+            (feed_id, cycles / requests)
+        })
+        .collect()
+}
+
+/// Surcharge in wei for the cycles at the current rate
+fn cycles_surcharge(cycles: u128) -> Nat {
+    STATE.with(|state| {
+        state
+            .borrow()
+            .metadata
+            .get()
+            .0
+            .cycles_surcharge(&Nat::from(cycles))
+    })
+}
+
+/// Fetches every requested feed once per tick, different feeds are fetched concurrently
+async fn fetch_data_feeds(
+    requests: &[ApolloCoordinatorRequest],
+) -> HashMap<String, Result<AssetDataResult, DataSourceError>> {
+    let feed_ids: BTreeSet<String> = requests
+        .iter()
+        .flat_map(ApolloCoordinatorRequest::feed_ids)
+        .collect();
+
+    if feed_ids.is_empty() {
+        return HashMap::new();
+    }
+
+    log!(
+        "[EXECUTION] chain: {}, fetching {} feeds",
+        get_metadata!(chain_id),
+        feed_ids.len()
+    );
+
+    let results = join_all(feed_ids.iter().cloned().map(get_fresh_feed)).await;
+
+    feed_ids.into_iter().zip(results).collect()
+}
+
+/// Fetches the feed from its data source, refetching it with a backoff if the price is older than the max staleness
+async fn get_fresh_feed(feed_id: String) -> Result<AssetDataResult, DataSourceError> {
+    let data_source = DataSource::from_feed_id(&feed_id, &get_metadata!(data_source_allowlist))?;
+    let max_staleness_sec =
+        STATE.with(|state| state.borrow().metadata.get().0.max_staleness_sec(&feed_id));
+
+    let mut attempt = 0;
+    loop {
+        let feed = data_source
+            .fetch(&feed_id, get_metadata!(sybil_canister_address))
+            .await?;
+
+        let (Some(max_staleness_sec), Some(timestamp)) = (max_staleness_sec, feed.data.timestamp())
+        else {
+            return Ok(feed);
+        };
+
+        if time::in_seconds().saturating_sub(timestamp) <= max_staleness_sec {
+            return Ok(feed);
+        }
+
+        attempt += 1;
         log!(
-            "[EXECUTION] chain: {}, requester: {}, used gas: {}, gas limit: {}",
+            "[EXECUTION] chain: {}, feed_id: {}, stale data, timestamp: {}, attempt: {}",
+            get_metadata!(chain_id),
+            feed_id,
+            timestamp,
+            attempt
+        );
+
+        if attempt >= MAX_STALE_DATA_ATTEMPTS {
+            return Err(DataSourceError::StaleData(timestamp, max_staleness_sec));
+        }
+
+        time::sleep(STALE_DATA_RETRY_DELAY * 2u32.pow(attempt - 1)).await;
+    }
+}
+
+/// Charges the requesters for the gas used by their callbacks in the mined multicall transaction,
+/// the fee and the surcharge for the attached cycles, which were fixed at send time.
+/// Gas is charged even if the callback reverted, since it was consumed by the AMA anyway
+fn bill_multicall(
+    receipt: &TransactionReceipt,
+    calls: &[PendingCall],
+    gas_price: &Nat,
+    fee_rate: Option<FeeConversionRate>,
+) -> Result<()> {
+    // nobody is charged if the results can't be matched to the calls
+    let results =
+        multicall::get_multicall_results(&get_metadata!(multicall_address), receipt, calls.len())?;
+    let tx_hash = format!("{:?}", receipt.transaction_hash);
+    let mut subscription_deliveries = vec![];
+
+    for (result, call) in results.into_iter().zip(calls) {
+        log!(
+            "[EXECUTION] chain: {}, requester: {}, success: {}, used gas: {}, gas limit: {}",
             get_metadata!(chain_id),
             call.target,
+            result.success,
             result.used_gas,
             call.gas_limit
         );
 
-        #[allow(clippy::cmp_owned)]
-        if result.used_gas == 0.into() {
-            panic!("used_gas is 0"); //TODO: check and remove
-        }
+        let mut used_gas = result.used_gas.to_nat();
+        if used_gas > call.gas_limit {
+            log!(
+                "[EXECUTION] chain: {}, requester: {}, used gas exceeds the gas limit, charging the gas limit",
+                get_metadata!(chain_id),
+                call.target
+            );
 
-        if result.used_gas > call.gas_limit {
-            panic!("used_gas is greater than gas_limit"); //TODO: check and remove
+            used_gas = call.gas_limit.clone();
         }
 
-        let amount = gas_price.to_nat() * result.used_gas.to_nat() + get_metadata!(apollos_fee);
+        let fee = call
+            .fee
+            .clone()
+            .unwrap_or_else(|| get_metadata!(apollos_fee));
+        let gas_cost = gas_price.clone() * used_gas.clone();
+        let amount = gas_cost.clone() + fee.clone() + call.surcharge.clone();
 
-        Balances::reduce_amount(
-            &Allowances::get_allowed_user(format!("{:?}", call.target))?,
-            &amount,
-        )
-        .expect("should reduce balance");
+        Stats::add_gas_spent(&gas_cost);
+
+        let charged = if call.prepaid {
+            // the requester was charged on the source chain
+            Nat::from(0)
+        } else if call.reserved > Nat::from(0) {
+            // the requester doesn't pay more than was reserved, the rest of the reservation is returned
+            let charged = amount.min(call.reserved.clone());
+            refund_reservation(&call.target, &(call.reserved.clone() - charged.clone()));
+
+            RequestVolumes::increment(&call.target);
+            Stats::add_fee(&fee);
+
+            charged
+        } else {
+            // the calls, which were sent before the reservations, are charged on settlement
+            match charge_requester(&call.target, &amount) {
+                Ok(()) => {
+                    RequestVolumes::increment(&call.target);
+                    Stats::add_fee(&fee);
+                    amount
+                }
+                Err(err) => {
+                    log!(
+                        "[EXECUTION] chain: {}, requester: {}, unable to charge {}: {}",
+                        get_metadata!(chain_id),
+                        call.target,
+                        amount,
+                        err
+                    );
+
+                    Nat::from(0)
+                }
+            }
+        };
+
+        CallbackResults::add(CallbackResult {
+            request_id: call.request_id,
+            requester: call.target.clone(),
+            success: result.success,
+            used_gas,
+            charged,
+            revert_reason: result.revert_reason(),
+            tx_hash: tx_hash.clone(),
+            executed_at: time::in_seconds(),
+            fee_rate: fee_rate.clone(),
+            cycles_spent: call.cycles.clone(),
+        });
+
+        // the heartbeat is delivered, even if the callback reverted
+        if let Some(subscription_id) = Subscription::id_from_request_id(call.request_id) {
+            subscription_deliveries.push((subscription_id, call.subscription_value));
+        }
     }
 
+    Subscriptions::mark_delivered(&subscription_deliveries, time::in_seconds());
+
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
+fn charge_requester(target: &str, amount: &Nat) -> Result<()> {
+    let user = Allowances::get_allowed_user(target.to_string())?;
+    Balances::reduce_amount(&user, amount)?;
 
-    use super::*;
+    Ok(())
+}
 
-    #[test]
-    fn test_target_function_abi() -> Result<()> {
-        let _ = serde_json::from_str::<Function>(TARGET_FUNCTION_ABI)?;
+fn refund_requester(target: &str, amount: &Nat) -> Result<()> {
+    let user = Allowances::get_allowed_user(target.to_string())?;
+    Balances::add_amount(&user, amount)?;
 
-        Ok(())
+    Ok(())
+}
+
+/// Returns the reserved amount, which wasn't spent on the callback
+fn refund_reservation(target: &str, amount: &Nat) {
+    if *amount == Nat::from(0) {
+        return;
+    }
+
+    if let Err(err) = refund_requester(target, amount) {
+        log!(
+            "[EXECUTION] chain: {}, requester: {}, unable to refund {}: {}",
+            get_metadata!(chain_id),
+            target,
+            amount,
+            err
+        );
+    }
+}
+
+/// Fee of the request in wei by the fee schedule, the requester's volume is the number of the charged callbacks
+fn request_fee(request: &ApolloCoordinatorRequest, fee_rate: Option<&FeeConversionRate>) -> Nat {
+    let feed_ids = match request {
+        // the feed is fetched by the target chain instance, but it is priced on this chain
+        ApolloCoordinatorRequest::CrossChainDataFeed { feed_id, .. } => vec![feed_id.clone()],
+        _ => request.feed_ids(),
+    };
+    let requests = RequestVolumes::get(&address::from_h160(&request.requester()));
+
+    let fee = STATE.with(|state| {
+        state
+            .borrow()
+            .metadata
+            .get()
+            .0
+            .fee(request.kind(), &feed_ids, requests)
+    });
+
+    match fee_rate {
+        Some(fee_rate) => fee_rate.to_native(fee),
+        None => fee,
+    }
+}
+
+/// Price of the native token, which converts the USD fees to wei, `None` if the fees are in wei
+async fn fee_conversion_rate() -> Result<Option<FeeConversionRate>> {
+    let FeeDenomination::UsdCents { price_feed_id } = get_metadata!(fee_denomination) else {
+        return Ok(None);
+    };
+
+    let feed = get_fresh_feed(price_feed_id.clone()).await?;
+
+    let (rate, decimals) = match feed.data {
+        SybilAssetData::DefaultPriceFeed { rate, decimals, .. }
+        | SybilAssetData::CustomPriceFeed { rate, decimals, .. } => (rate, decimals),
+        _ => return Err(anyhow!("{} is not a price feed", price_feed_id)),
+    };
+
+    if rate == 0 {
+        return Err(anyhow!("{} has a zero rate", price_feed_id));
+    }
+
+    Ok(Some(FeeConversionRate {
+        price_feed_id,
+        rate,
+        decimals,
+    }))
+}
+
+/// Records the request, which was not delivered to the requester
+fn record_failed_request(request_id: u64, requester: &H160, reason: &str) {
+    CallbackResults::add(CallbackResult {
+        request_id,
+        requester: address::from_h160(requester),
+        success: false,
+        used_gas: Nat::from(0),
+        charged: Nat::from(0),
+        revert_reason: Some(reason.to_string()),
+        tx_hash: String::new(),
+        executed_at: time::in_seconds(),
+        fee_rate: None,
+        cycles_spent: Nat::from(0),
+    });
+}
+
+/// Records the callbacks of the multicall transaction, which failed or was cancelled
+fn record_unexecuted_calls(calls: &[PendingCall], tx_hash: String, reason: &str) {
+    for call in calls {
+        if !call.prepaid {
+            refund_reservation(&call.target, &call.reserved);
+        }
+
+        CallbackResults::add(CallbackResult {
+            request_id: call.request_id,
+            requester: call.target.clone(),
+            success: false,
+            used_gas: Nat::from(0),
+            charged: Nat::from(0),
+            revert_reason: Some(reason.to_string()),
+            tx_hash: tx_hash.clone(),
+            executed_at: time::in_seconds(),
+            fee_rate: None,
+            cycles_spent: call.cycles.clone(),
+        });
     }
 }
@@ -0,0 +1,278 @@
+use std::str::FromStr;
+
+use anyhow::Result;
+use apollo_utils::{
+    address, get_metadata, log,
+    nat::ToNativeTypes,
+    time,
+    web3::{self, Web3Instance, TRANSFER_GAS_LIMIT},
+};
+use ic_web3_rs::{
+    types::{TransactionReceipt, H256, U256},
+    Transport,
+};
+
+use crate::{
+    types::nonce_manager::{InFlightTx, NonceManager, TxPurpose},
+    utils::apollo_evm_address,
+};
+
+use super::{bill_multicall, record_unexecuted_calls, withdraw};
+
+// Resubmit the transaction with a bumped gas price if it is not mined during this period
+const SPEED_UP_AFTER_SEC: u64 = 2 * 60;
+// Replace the transaction with a zero-value self-transfer if it is not mined during this period
+const CANCEL_AFTER_SEC: u64 = 10 * 60;
+const MAX_SPEED_UPS: u32 = 5;
+// Stop tracking the transaction, if its nonce is used without a known receipt or it can't be replaced anymore
+const ABANDON_AFTER_SEC: u64 = 60 * 60;
+const TX_SUCCESS_STATUS: u64 = 1;
+
+pub async fn execute() {
+    if let Err(err) = manage_in_flight_txs().await {
+        log!("[NONCE MANAGER] Error while managing in-flight txs: {err}");
+    }
+}
+
+async fn manage_in_flight_txs() -> Result<()> {
+    let in_flight_txs = NonceManager::get_all();
+    if in_flight_txs.is_empty() {
+        return Ok(());
+    }
+
+    let w3 = web3::instance(get_metadata!(chain_rpc), get_metadata!(evm_rpc_canister))?;
+    let ama = apollo_evm_address().await?;
+
+    let chain_nonce = w3.get_nonce(&ama).await?.as_u64();
+
+    let (mined, pending): (Vec<InFlightTx>, Vec<InFlightTx>) = in_flight_txs
+        .into_iter()
+        .partition(|tx| tx.nonce < chain_nonce);
+
+    for tx in mined {
+        if let Err(err) = process_mined_tx(&w3, &tx).await {
+            log!(
+                "[NONCE MANAGER] Unable to process mined tx with nonce {}: {err}",
+                tx.nonce
+            );
+        }
+    }
+
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let gas_price = w3.get_gas_price().await?;
+    let now = time::in_seconds();
+
+    for tx in pending {
+        let result = if tx.is_cancelled()
+            && tx.speed_ups >= MAX_SPEED_UPS
+            && now.saturating_sub(tx.last_sent_at) >= ABANDON_AFTER_SEC
+        {
+            abandon(&tx, "transaction was not mined after the cancellation")
+        } else if !tx.is_cancelled() && now - tx.first_sent_at >= CANCEL_AFTER_SEC {
+            cancel(&w3, &tx, &ama, gas_price).await
+        } else if now - tx.last_sent_at >= SPEED_UP_AFTER_SEC && tx.speed_ups < MAX_SPEED_UPS {
+            if tx.is_cancelled() {
+                cancel(&w3, &tx, &ama, gas_price).await
+            } else {
+                speed_up(&w3, &tx, &ama, gas_price).await
+            }
+        } else {
+            Ok(())
+        };
+
+        if let Err(err) = result {
+            log!(
+                "[NONCE MANAGER] Unable to replace tx with nonce {}: {err}",
+                tx.nonce
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds which of the submissions was mined and finalizes the transaction purpose
+async fn process_mined_tx<T: Transport>(w3: &Web3Instance<T>, tx: &InFlightTx) -> Result<()> {
+    let Some((receipt, is_cancel)) = find_receipt(w3, tx).await? else {
+        // the nonce was taken by a transaction, which is not tracked, e.g. a dropped replacement
+        if time::in_seconds().saturating_sub(tx.last_sent_at) >= ABANDON_AFTER_SEC {
+            return abandon(tx, "nonce was used by another transaction");
+        }
+
+        log!(
+            "[NONCE MANAGER] Nonce {} is used, but the receipt is not available yet",
+            tx.nonce
+        );
+        return Ok(());
+    };
+
+    NonceManager::remove(tx.nonce);
+
+    let is_success = receipt
+        .status
+        .map(|status| status.as_u64() == TX_SUCCESS_STATUS)
+        .unwrap_or_default();
+
+    log!(
+        "[NONCE MANAGER] Tx mined: nonce = {}, hash = {:?}, success = {}, cancel = {}",
+        tx.nonce,
+        receipt.transaction_hash,
+        is_success,
+        is_cancel
+    );
+
+    if is_success && !is_cancel {
+        match &tx.purpose {
+            TxPurpose::Multicall {
+                calls,
+                gas_price,
+                fee_rate,
+            } => bill_multicall(&receipt, calls, gas_price, fee_rate.clone())?,
+            TxPurpose::Multitransfer { .. } | TxPurpose::Unknown => {}
+        }
+
+        return Ok(());
+    }
+
+    match &tx.purpose {
+        TxPurpose::Multicall { calls, .. } => {
+            let reason = if is_cancel {
+                "multicall transaction was cancelled"
+            } else {
+                "multicall transaction failed"
+            };
+
+            record_unexecuted_calls(calls, format!("{:?}", receipt.transaction_hash), reason)
+        }
+        TxPurpose::Multitransfer { transfers } => withdraw::refund(transfers)?,
+        TxPurpose::Unknown => {}
+    }
+
+    Ok(())
+}
+
+/// Stops tracking the transaction, which won't be processed anymore.
+/// Its callbacks are recorded as unexecuted and its transfers are refunded
+fn abandon(tx: &InFlightTx, reason: &str) -> Result<()> {
+    log!(
+        "[NONCE MANAGER] Tx abandoned: nonce = {}, reason = {}",
+        tx.nonce,
+        reason
+    );
+
+    NonceManager::remove(tx.nonce);
+
+    match &tx.purpose {
+        TxPurpose::Multicall { calls, .. } => record_unexecuted_calls(
+            calls,
+            tx.tx_hashes.last().cloned().unwrap_or_default(),
+            reason,
+        ),
+        TxPurpose::Multitransfer { transfers } => withdraw::refund(transfers)?,
+        TxPurpose::Unknown => {}
+    }
+
+    Ok(())
+}
+
+async fn find_receipt<T: Transport>(
+    w3: &Web3Instance<T>,
+    tx: &InFlightTx,
+) -> Result<Option<(TransactionReceipt, bool)>> {
+    let submissions = tx
+        .tx_hashes
+        .iter()
+        .map(|hash| (hash, false))
+        .chain(tx.cancel_tx_hashes.iter().map(|hash| (hash, true)));
+
+    // the most recent submissions are the most likely to be mined
+    for (hash, is_cancel) in submissions.rev() {
+        if let Some(receipt) = w3.get_tx_receipt(&H256::from_str(hash)?).await? {
+            return Ok(Some((receipt, is_cancel)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Resubmits the same transaction with a bumped gas price
+async fn speed_up<T: Transport>(
+    w3: &Web3Instance<T>,
+    tx: &InFlightTx,
+    ama: &str,
+    gas_price: U256,
+) -> Result<()> {
+    let sent_tx = w3
+        .send_transaction(
+            address::to_h160(&tx.to)?,
+            tx.data.clone(),
+            tx.value.to_u256(),
+            tx.gas.to_u256(),
+            bump_gas_price(tx.gas_price.to_u256(), gas_price),
+            U256::from(tx.nonce),
+            ama.to_string(),
+            get_metadata!(key_name),
+            get_metadata!(chain_id).to_u64(),
+        )
+        .await?;
+
+    NonceManager::track_replacement(sent_tx, false);
+
+    Ok(())
+}
+
+/// Replaces the transaction with a zero-value self-transfer, which frees the nonce
+async fn cancel<T: Transport>(
+    w3: &Web3Instance<T>,
+    tx: &InFlightTx,
+    ama: &str,
+    gas_price: U256,
+) -> Result<()> {
+    let sent_tx = w3
+        .send_transaction(
+            address::to_h160(ama)?,
+            vec![],
+            U256::zero(),
+            U256::from(TRANSFER_GAS_LIMIT),
+            bump_gas_price(tx.gas_price.to_u256(), gas_price),
+            U256::from(tx.nonce),
+            ama.to_string(),
+            get_metadata!(key_name),
+            get_metadata!(chain_id).to_u64(),
+        )
+        .await?;
+
+    NonceManager::track_replacement(sent_tx, true);
+
+    Ok(())
+}
+
+/// Replacement transaction must have a gas price at least 10% higher than the previous one,
+/// so we take 1.2 of either the previous gas price or the current one, whichever is higher
+fn bump_gas_price(previous: U256, current: U256) -> U256 {
+    let bumped = previous * 12 / 10 + 1;
+    let current = current * 12 / 10;
+
+    bumped.max(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bump_gas_price() {
+        assert_eq!(
+            bump_gas_price(U256::from(100), U256::from(50)),
+            U256::from(121)
+        );
+        assert_eq!(
+            bump_gas_price(U256::from(100), U256::from(200)),
+            U256::from(240)
+        );
+        assert!(bump_gas_price(U256::from(1), U256::zero()) > U256::from(1));
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use apollo_utils::{
+    address,
+    apollo_instance::{FeeConversionRate, RelayedRequest},
+    errors::{ApolloError, ApolloInstanceError},
+    get_metadata, log,
+    nat::ToNatType,
+    retry_until_success,
+};
+use candid::{Nat, Principal};
+use ic_web3_rs::types::U256;
+
+use crate::types::{
+    request_volumes::RequestVolumes, stats::Stats, ApolloCoordinatorRequest, STATE,
+};
+
+use super::{charge_requester, record_failed_request, refund_requester, request_fee};
+
+/// Sends the cross-chain requests to the target chain instances through the factory.
+/// The requester is charged for the callback gas limit at the gas price of the target chain before the relay,
+/// and refunded if the request wasn't relayed
+pub async fn relay_requests(
+    requests: Vec<ApolloCoordinatorRequest>,
+    fee_rate: Option<&FeeConversionRate>,
+) {
+    // target chain id => gas price, it is fetched once per tick
+    let mut gas_prices: HashMap<U256, Nat> = HashMap::new();
+
+    for request in requests {
+        let fee = request_fee(&request, fee_rate);
+
+        let ApolloCoordinatorRequest::CrossChainDataFeed {
+            request_id,
+            feed_id,
+            callback_gas_limit,
+            requester,
+            target_chain_id,
+            target,
+        } = request
+        else {
+            continue;
+        };
+
+        let gas_price = match gas_prices.get(&target_chain_id) {
+            Some(gas_price) => gas_price.clone(),
+            None => match get_gas_price(target_chain_id.to_nat()).await {
+                Ok(gas_price) => {
+                    gas_prices.insert(target_chain_id, gas_price.clone());
+                    gas_price
+                }
+                Err(err) => {
+                    log!(
+                        "[RELAY] chain: {}, unable to get the gas price of chain {}: {}",
+                        get_metadata!(chain_id),
+                        target_chain_id,
+                        err
+                    );
+
+                    record_failed_request(request_id.as_u64(), &requester, &err.to_string());
+                    continue;
+                }
+            },
+        };
+
+        let amount = gas_price * callback_gas_limit.to_nat() + fee.clone();
+        let requester_address = address::from_h160(&requester);
+
+        // the amount is reserved, so it can't be withdrawn while the request is relayed
+        if let Err(err) = charge_requester(&requester_address, &amount) {
+            log!(
+                "[RELAY] chain: {}, requester: {}, unable to charge {}: {}",
+                get_metadata!(chain_id),
+                requester,
+                amount,
+                err
+            );
+
+            record_failed_request(request_id.as_u64(), &requester, &err.to_string());
+            continue;
+        }
+
+        let relayed_request = RelayedRequest {
+            source_chain_id: get_metadata!(chain_id),
+            target_chain_id: target_chain_id.to_nat(),
+            request_id: request_id.to_nat(),
+            feed_id,
+            callback_gas_limit: callback_gas_limit.to_nat(),
+            target: address::from_h160(&target),
+        };
+
+        let relayed_request_id = match relay_request(relayed_request).await {
+            Ok(relayed_request_id) => relayed_request_id,
+            Err(err) => {
+                log!(
+                    "[RELAY] chain: {}, requester: {}, unable to relay request {}: {}",
+                    get_metadata!(chain_id),
+                    requester,
+                    request_id,
+                    err
+                );
+
+                if let Err(err) = refund_requester(&requester_address, &amount) {
+                    log!(
+                        "[RELAY] chain: {}, requester: {}, unable to refund {}: {}",
+                        get_metadata!(chain_id),
+                        requester,
+                        amount,
+                        err
+                    );
+                }
+
+                record_failed_request(request_id.as_u64(), &requester, &err.to_string());
+                continue;
+            }
+        };
+
+        RequestVolumes::increment(&requester_address);
+        Stats::add_fee(&fee);
+
+        log!(
+            "[RELAY] chain: {}, request {} relayed to chain {} as {}",
+            get_metadata!(chain_id),
+            request_id,
+            target_chain_id,
+            relayed_request_id
+        );
+    }
+}
+
+/// Returns the id of the request on the target chain
+async fn relay_request(request: RelayedRequest) -> Result<u64, ApolloInstanceError> {
+    let apollo_canister = apollo_canister()?;
+
+    let (result,): (Result<u64, ApolloError>,) = retry_until_success!(ic_cdk::call(
+        apollo_canister,
+        "relay_request",
+        (request.clone(),)
+    ))
+    .map_err(|(_, msg)| ApolloInstanceError::FailedToRelayRequest(msg))?;
+
+    result.map_err(|err| ApolloInstanceError::FailedToRelayRequest(err.to_string()))
+}
+
+async fn get_gas_price(target_chain_id: Nat) -> Result<Nat, ApolloInstanceError> {
+    let apollo_canister = apollo_canister()?;
+
+    let (result,): (Result<Nat, ApolloError>,) = retry_until_success!(ic_cdk::call(
+        apollo_canister,
+        "get_chain_gas_price",
+        (get_metadata!(chain_id), target_chain_id.clone())
+    ))
+    .map_err(|(_, msg)| ApolloInstanceError::FailedToRelayRequest(msg))?;
+
+    result.map_err(|err| ApolloInstanceError::FailedToRelayRequest(err.to_string()))
+}
+
+fn apollo_canister() -> Result<Principal, ApolloInstanceError> {
+    let apollo_canister = STATE
+        .with(|state| state.borrow().apollo_canister.clone())
+        .ok_or_else(|| {
+            ApolloInstanceError::FailedToRelayRequest("apollo canister is not set".to_string())
+        })?;
+
+    Principal::from_text(apollo_canister)
+        .map_err(|err| ApolloInstanceError::FailedToRelayRequest(err.to_string()))
+}
@@ -0,0 +1,81 @@
+use apollo_utils::{
+    apollo_instance::SolvencyStatus, errors::ApolloInstanceError, get_metadata, log,
+    nat::ToNatType, time, update_state, web3,
+};
+
+use crate::{
+    types::{balances::Balances, STATE},
+    utils::apollo_evm_address,
+};
+
+/// Checks the AMA balance against the user balances and the configured minimum.
+/// Returns `false`, if the fulfillment should be paused
+pub async fn check() -> bool {
+    let status = match get_status().await {
+        Ok(status) => status,
+        Err(err) => {
+            // the last known status is kept, when the balance can't be fetched.
+            // The fulfillment is paused, if the AMA was never checked
+            let was_solvent = STATE.with(|state| {
+                state
+                    .borrow()
+                    .solvency
+                    .as_ref()
+                    .is_some_and(|status| status.is_solvent)
+            });
+
+            log!(
+                "[SOLVENCY] chain: {}, unable to check the AMA balance, keeping the last known status (solvent: {}): {}",
+                get_metadata!(chain_id),
+                was_solvent,
+                err
+            );
+
+            return was_solvent;
+        }
+    };
+
+    let was_solvent = STATE.with(|state| {
+        state
+            .borrow()
+            .solvency
+            .as_ref()
+            .map_or(true, |status| status.is_solvent)
+    });
+
+    if was_solvent && !status.is_solvent {
+        log!(
+            "[SOLVENCY] ALERT chain: {}, AMA is insolvent, AMA balance: {}, user balances: {}, min AMA balance: {}",
+            get_metadata!(chain_id),
+            status.ama_balance,
+            status.total_user_balances,
+            status.min_ama_balance
+        );
+    } else if !was_solvent && status.is_solvent {
+        log!(
+            "[SOLVENCY] chain: {}, AMA is solvent again, AMA balance: {}",
+            get_metadata!(chain_id),
+            status.ama_balance
+        );
+    }
+
+    let is_solvent = status.is_solvent;
+    update_state!(solvency, Some(status));
+
+    is_solvent
+}
+
+async fn get_status() -> Result<SolvencyStatus, ApolloInstanceError> {
+    let w3 = web3::instance(get_metadata!(chain_rpc), get_metadata!(evm_rpc_canister))?;
+    let ama_balance = w3
+        .get_address_balance(&apollo_evm_address().await?)
+        .await?
+        .to_nat();
+
+    Ok(SolvencyStatus::new(
+        ama_balance,
+        Balances::total(),
+        get_metadata!(min_ama_balance),
+        time::in_seconds(),
+    ))
+}
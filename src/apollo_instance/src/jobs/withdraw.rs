@@ -2,7 +2,7 @@ use std::str::FromStr;
 
 use anyhow::Result;
 use apollo_utils::{
-    get_metadata, log,
+    address, get_metadata, log,
     multicall::{self, MultitransferArgs, Transfer},
     nat::{ToNatType, ToNativeTypes},
     web3,
@@ -12,6 +12,7 @@ use ic_web3_rs::types::{H160, U256};
 use crate::{
     types::{
         balances::Balances,
+        nonce_manager::{NonceManager, PendingTransfer, TxPurpose},
         withdraw::{WithdrawRequest, WithdrawRequests},
     },
     utils::apollo_evm_address,
@@ -59,6 +60,7 @@ async fn send_funds(reqs: &[WithdrawRequest]) -> Result<()> {
     log!("Transfers: {:#?}", transfers);
 
     let w3 = web3::instance(get_metadata!(chain_rpc), get_metadata!(evm_rpc_canister))?;
+    let ama = apollo_evm_address().await?;
 
     for transfers_chunk in transfers.chunks(MAX_TRANSFERS) {
         // multiply the gas_price to 1.2 to avoid long transaction confirmation
@@ -71,27 +73,69 @@ async fn send_funds(reqs: &[WithdrawRequest]) -> Result<()> {
             gas_price.clone(),
             multitransfer_args.clone(),
             &get_metadata!(multicall_address),
-            apollo_evm_address().await?,
+            ama.clone(),
         )
         .await?;
 
         multitransfer_args.retain_sufficient(gas * gas_price);
 
-        multicall::multitransfer(
+        let sent_tx = multicall::multitransfer(
             &w3,
             gas_price,
             gas,
             get_metadata!(chain_id).to_u64(),
             multitransfer_args.clone(),
             &get_metadata!(multicall_address),
-            apollo_evm_address().await?,
+            ama.clone(),
             get_metadata!(key_name),
+            NonceManager::next_nonce(w3.get_nonce(&ama).await?),
         )
         .await?;
 
-        for transfer in multitransfer_args.transfers {
-            Balances::reduce_amount(&transfer.from, &transfer.value.to_nat())?;
-        }
+        // the tx is broadcast, so it is tracked even if some balances can't be reduced,
+        // only the reduced transfers are refunded, if the tx fails
+        let transfers: Vec<PendingTransfer> = multitransfer_args
+            .transfers
+            .into_iter()
+            .map(|transfer| PendingTransfer {
+                from: transfer.from,
+                receiver: address::from_h160(&transfer.target),
+                amount: transfer.value.to_nat(),
+            })
+            .filter(
+                |transfer| match Balances::reduce_amount(&transfer.from, &transfer.amount) {
+                    Ok(()) => true,
+                    Err(err) => {
+                        log!(
+                            "[WITHDRAWER] Unable to reduce {} from {}: {}",
+                            transfer.amount,
+                            transfer.from,
+                            err
+                        );
+
+                        false
+                    }
+                },
+            )
+            .collect();
+
+        NonceManager::track(sent_tx, TxPurpose::Multitransfer { transfers });
+    }
+
+    Ok(())
+}
+
+/// Returns the funds of the failed or cancelled multitransfer to the users balances
+pub fn refund(transfers: &[PendingTransfer]) -> Result<()> {
+    for transfer in transfers {
+        log!(
+            "[WITHDRAWER] Refunding {} to {}, transfer to {} failed",
+            transfer.amount,
+            transfer.from,
+            transfer.receiver
+        );
+
+        Balances::add_amount(&transfer.from, &transfer.amount)?;
     }
 
     Ok(())
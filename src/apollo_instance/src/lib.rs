@@ -1,7 +1,7 @@
 use apollo_utils::memory::Cbor;
 use apollo_utils::{apollo_instance::ApolloInstanceInit, errors::ApolloInstanceError, log};
 use candid::Nat;
-use types::STATE;
+use types::{coordinators::Coordinators, STATE};
 use utils::set_custom_panic_hook;
 
 mod jobs;
@@ -18,8 +18,13 @@ fn init(args: ApolloInstanceInit) {
     STATE.with(|s| {
         let mut state = s.borrow_mut();
         state.timer_frequency_sec = args.timer_frequency_sec;
+        // instances are created by the factory
+        state.apollo_canister = Some(ic_cdk::caller().to_text());
         state.metadata.set(Cbor(args.into())).unwrap();
     });
+
+    // the coordinator from the init args is watched from the latest block
+    Coordinators::migrate();
 }
 
 // For candid file auto-generation
@@ -28,8 +33,19 @@ pub type NatResult = std::result::Result<Nat, ApolloInstanceError>;
 pub type StringResult = std::result::Result<String, ApolloInstanceError>;
 
 use apollo_utils::apollo_instance::ApolloInstanceMetadata;
+use apollo_utils::apollo_instance::ApolloInstanceStats;
+use apollo_utils::apollo_instance::CoordinatorAbiVersion;
+use apollo_utils::apollo_instance::RelayedRequest;
+use apollo_utils::apollo_instance::RequestKind;
+use apollo_utils::apollo_instance::SolvencyStatus;
 use apollo_utils::apollo_instance::UpdateMetadata;
 use candid::Principal;
+use types::callback_layouts::CallbackLayout;
+use types::callback_results::CallbackResult;
+use types::coordinators::Coordinator;
+use types::nonce_manager::InFlightTx;
+use types::subscriptions::{Subscription, SubscriptionRequest};
+use types::unknown_events::UnknownEvent;
 
 candid::export_service!();
 
@@ -12,6 +12,20 @@ const BALANCES_MEMORY_ID: MemoryId = MemoryId::new(2);
 const WITHDRAW_REQUESTS_MEMORY_ID: MemoryId = MemoryId::new(3);
 // A memory for allowed contracts
 const ALLOWANCES_MEMORY_ID: MemoryId = MemoryId::new(4);
+// A memory for transactions sent from the AMA which are not confirmed yet
+const IN_FLIGHT_TXS_MEMORY_ID: MemoryId = MemoryId::new(5);
+// A memory for results of the executed callbacks
+const CALLBACK_RESULTS_MEMORY_ID: MemoryId = MemoryId::new(6);
+// A memory for custom callback layouts of the requesters' contracts
+const CALLBACK_LAYOUTS_MEMORY_ID: MemoryId = MemoryId::new(7);
+// A memory for the coordinator events, which were skipped by the logs polling
+const UNKNOWN_EVENTS_MEMORY_ID: MemoryId = MemoryId::new(8);
+// A memory for the feed subscriptions of the contracts
+const SUBSCRIPTIONS_MEMORY_ID: MemoryId = MemoryId::new(9);
+// A memory for the watched coordinator contracts
+const COORDINATORS_MEMORY_ID: MemoryId = MemoryId::new(10);
+// A memory for the number of the charged callbacks per requester
+const REQUEST_VOLUMES_MEMORY_ID: MemoryId = MemoryId::new(11);
 
 pub type VMemory = VirtualMemory<DefaultMemoryImpl>;
 
@@ -41,3 +55,31 @@ pub fn get_withdraw_requests_memory() -> VMemory {
 pub fn get_allowances_memory() -> VMemory {
     MEMORY_MANAGER.with(|m| m.borrow().get(ALLOWANCES_MEMORY_ID))
 }
+
+pub fn get_in_flight_txs_memory() -> VMemory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(IN_FLIGHT_TXS_MEMORY_ID))
+}
+
+pub fn get_callback_results_memory() -> VMemory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(CALLBACK_RESULTS_MEMORY_ID))
+}
+
+pub fn get_callback_layouts_memory() -> VMemory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(CALLBACK_LAYOUTS_MEMORY_ID))
+}
+
+pub fn get_unknown_events_memory() -> VMemory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(UNKNOWN_EVENTS_MEMORY_ID))
+}
+
+pub fn get_subscriptions_memory() -> VMemory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(SUBSCRIPTIONS_MEMORY_ID))
+}
+
+pub fn get_coordinators_memory() -> VMemory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(COORDINATORS_MEMORY_ID))
+}
+
+pub fn get_request_volumes_memory() -> VMemory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(REQUEST_VOLUMES_MEMORY_ID))
+}
@@ -1,5 +1,7 @@
 use apollo_utils::{
     address,
+    apollo_instance::{RequestKind, SolvencyStatus},
+    canister::validate_caller,
     errors::{ApolloInstanceError, Web3Error},
     get_metadata, log,
     nat::ToNatType,
@@ -10,7 +12,10 @@ use ic_cdk::{query, update};
 
 use crate::{
     jobs::withdraw,
-    types::{allowances::Allowances, balances::Balances, timer::Timer, withdraw::WithdrawRequests},
+    types::{
+        allowances::Allowances, balances::Balances, request_volumes::RequestVolumes, stats::Stats,
+        timer::Timer, withdraw::WithdrawRequests, STATE,
+    },
     utils::apollo_evm_address,
     NatResult, Result,
 };
@@ -29,6 +34,55 @@ pub fn get_balance(address: String) -> NatResult {
     Ok(Balances::get(&address).unwrap_or_default().amount)
 }
 
+/// Get the native token balance of the AMA on the chain.
+/// It spends the instance cycles on the RPC call, so it is restricted to the controllers,
+/// `get_solvency_status` returns the last checked balance to everyone
+#[candid_method]
+#[update]
+pub async fn get_ama_balance() -> NatResult {
+    validate_caller()?;
+
+    let w3 = web3::instance(get_metadata!(chain_rpc), get_metadata!(evm_rpc_canister))?;
+
+    Ok(w3
+        .get_address_balance(&apollo_evm_address().await?)
+        .await?
+        .to_nat())
+}
+
+/// Get the last AMA solvency check, `None` if the AMA was not checked yet
+#[candid_method]
+#[query]
+pub fn get_solvency_status() -> Option<SolvencyStatus> {
+    STATE.with(|state| state.borrow().solvency.clone())
+}
+
+/// Preview the fee, which is charged on top of the used gas.
+/// The fee is in the fee denomination, USD fees are converted to wei at charge time
+///
+/// # Arguments
+/// * `requester` - Address of the requester's contract, its volume discount is applied
+/// * `kind` - Kind of the request
+/// * `feed_ids` - Requested feeds, empty for the random requests
+///
+/// # Returns
+///
+/// Returns a result with the fee by the current fee schedule
+#[candid_method]
+#[query]
+pub fn preview_fee(requester: String, kind: RequestKind, feed_ids: Vec<String>) -> NatResult {
+    let requests = RequestVolumes::get(&address::normalize(&requester)?);
+
+    Ok(STATE.with(|state| {
+        state
+            .borrow()
+            .metadata
+            .get()
+            .0
+            .fee(kind, &feed_ids, requests)
+    }))
+}
+
 /// Deposit amount to the AMA
 ///
 /// # Arguments
@@ -68,6 +122,7 @@ pub async fn deposit(
     let amount = tx.value.to_nat();
 
     Balances::add_amount(&sender, &amount)?;
+    Stats::add_deposit(&amount);
 
     if let Some(contract) = allowance {
         Allowances::grant(contract.clone(), sender.clone())?;
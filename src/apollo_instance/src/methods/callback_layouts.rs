@@ -0,0 +1,66 @@
+use crate::{
+    types::{
+        allowances::Allowances,
+        callback_layouts::{CallbackLayout, CallbackLayouts},
+    },
+    Result,
+};
+use apollo_utils::{address, canister::validate_caller, errors::ApolloInstanceError, log};
+use candid::candid_method;
+use ic_cdk::{query, update};
+
+/// Set the function, which receives the requested data, for the contract
+///
+/// # Arguments
+///
+/// * `address` - Address of the contract, which must be granted to use the signer's balance
+/// * `signature` - Signature of the function, e.g. `fulfill(uint256,string,uint256,uint256,uint256)`, `None` to use the default `rawFulfillData(bytes)`
+/// * `msg` - SIWE message, For more information, refer to the [SIWE message specification](https://eips.ethereum.org/EIPS/eip-4361)
+/// * `sig` - SIWE signature, For more information, refer to the [SIWE message specification](https://eips.ethereum.org/EIPS/eip-4361)
+///
+/// # Returns
+///
+/// Returns a result that can contain an error message
+
+#[candid_method]
+#[update]
+pub async fn set_callback_layout(
+    address: String,
+    signature: Option<String>,
+    msg: String,
+    sig: String,
+) -> Result<()> {
+    let user = address::normalize(&apollo_utils::siwe::recover(msg, sig).await)?;
+
+    if Allowances::get_allowed_user(address.clone())? != user {
+        return Err(ApolloInstanceError::ContractIsNotGranted(address));
+    }
+
+    set_layout(&address, signature)?;
+
+    log!("[CALLBACK LAYOUTS] {user} updated the callback layout of {address}");
+    Ok(())
+}
+
+/// Set the function, which receives the requested data, for the contract on behalf of the coordinator
+#[candid_method]
+#[update]
+pub fn set_callback_layout_as_controller(address: String, signature: Option<String>) -> Result<()> {
+    validate_caller()?;
+
+    set_layout(&address, signature)
+}
+
+#[candid_method]
+#[query]
+pub fn get_callback_layout(address: String) -> Result<CallbackLayout> {
+    CallbackLayouts::get(&address)
+}
+
+fn set_layout(address: &str, signature: Option<String>) -> Result<()> {
+    let layout = signature
+        .map(|signature| CallbackLayout::new(&signature))
+        .transpose()?;
+
+    CallbackLayouts::set(address, layout)
+}
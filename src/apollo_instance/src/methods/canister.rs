@@ -1,6 +1,15 @@
-use crate::{types::STATE, utils::apollo_evm_address, Result};
+use crate::{
+    types::{
+        coordinators::Coordinators,
+        nonce_manager::{InFlightTx, NonceManager},
+        stats::{Stats, TickCycles},
+        STATE,
+    },
+    utils::apollo_evm_address,
+    Result,
+};
 use apollo_utils::{
-    apollo_instance::{ApolloInstanceMetadata, UpdateMetadata},
+    apollo_instance::{ApolloInstanceMetadata, ApolloInstanceStats, UpdateMetadata},
     canister::validate_caller,
     errors::ApolloInstanceError,
     get_state, log,
@@ -71,7 +80,8 @@ fn update_timer_frequency_sec(timer_frequency_sec: u64) -> Result<()> {
 async fn update_last_parsed_logs_from_block(block_number: Option<u64>) -> Result<()> {
     validate_caller()?;
 
-    update_state!(last_parsed_logs_from_block, block_number);
+    // the cursor is moved for every coordinator
+    Coordinators::set_last_parsed_block(None, block_number);
     Ok(())
 }
 
@@ -80,3 +90,24 @@ async fn update_last_parsed_logs_from_block(block_number: Option<u64>) -> Result
 async fn get_apollo_address() -> Result<String> {
     Ok(apollo_evm_address().await?)
 }
+
+/// Returns transactions sent from the AMA, which are not mined yet
+#[candid_method]
+#[query]
+fn get_in_flight_txs() -> Vec<InFlightTx> {
+    NonceManager::get_all()
+}
+
+/// Totals of the deposits, charged fees and the gas paid by the AMA
+#[candid_method]
+#[query]
+fn get_stats() -> ApolloInstanceStats {
+    Stats::get()
+}
+
+/// Cycles, which were spent by the last ticks
+#[candid_method]
+#[query]
+fn get_tick_cycles() -> Vec<TickCycles> {
+    Stats::get_ticks()
+}
@@ -0,0 +1,44 @@
+use crate::{
+    types::coordinators::{Coordinator, Coordinators},
+    Result,
+};
+use apollo_utils::{apollo_instance::CoordinatorAbiVersion, canister::validate_caller};
+use candid::candid_method;
+use ic_cdk::{query, update};
+
+/// Starts watching the coordinator contract for the requests
+///
+/// # Arguments
+///
+/// * `address` - Address of the coordinator contract
+/// * `abi_version` - ABI version of the coordinator, `V1` by default
+/// * `from_block` - Block, from which the logs are parsed, the latest block by default
+#[candid_method]
+#[update]
+pub fn add_coordinator(
+    address: String,
+    abi_version: Option<CoordinatorAbiVersion>,
+    from_block: Option<u64>,
+) -> Result<()> {
+    validate_caller()?;
+
+    // the cursor points to the last parsed block
+    let last_parsed_block = from_block.map(|block| block.saturating_sub(1));
+
+    Coordinators::add(&address, abi_version.unwrap_or_default(), last_parsed_block)
+}
+
+/// Stops watching the coordinator contract, its pending requests are not processed anymore
+#[candid_method]
+#[update]
+pub fn remove_coordinator(address: String) -> Result<()> {
+    validate_caller()?;
+
+    Coordinators::remove(&address)
+}
+
+#[candid_method]
+#[query]
+pub fn get_coordinators() -> Vec<Coordinator> {
+    Coordinators::get_all()
+}
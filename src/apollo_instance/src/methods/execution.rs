@@ -1,11 +1,19 @@
+use apollo_utils::apollo_instance::RelayedRequest;
 use apollo_utils::canister::validate_caller;
 use apollo_utils::errors::ApolloInstanceError;
-use apollo_utils::log;
-use candid::candid_method;
-use ic_cdk::update;
+use apollo_utils::nat::ToNatType;
+use apollo_utils::{get_metadata, log, web3};
+use candid::{candid_method, Nat, Principal};
+use ic_cdk::{query, update};
 
-use crate::Result;
-use crate::{jobs::execute, types::timer::Timer};
+use crate::types::callback_results::{CallbackResult, CallbackResults};
+use crate::types::unknown_events::{UnknownEvent, UnknownEvents};
+use crate::types::{RELAYED_REQUEST_ID_OFFSET, STATE};
+use crate::{
+    jobs::{execute, gas_price},
+    types::timer::Timer,
+};
+use crate::{NatResult, Result};
 
 #[candid_method]
 #[update]
@@ -45,3 +53,74 @@ pub fn stop() -> Result<()> {
 
     Ok(())
 }
+
+#[candid_method]
+#[query]
+pub fn get_callback_result(request_id: u64) -> Option<CallbackResult> {
+    CallbackResults::get(request_id)
+}
+
+#[candid_method]
+#[query]
+pub fn get_unknown_events() -> Vec<UnknownEvent> {
+    UnknownEvents::get_all()
+}
+
+/// Add the request from another chain, which is delivered on the next tick.
+/// Called by the factory, the requester is already charged on the source chain
+///
+/// # Returns
+///
+/// Returns the id, under which the request is delivered and its callback result is stored
+#[candid_method]
+#[update]
+pub fn add_relayed_request(request: RelayedRequest) -> Result<u64> {
+    validate_caller()?;
+
+    let request_id = STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let request_id = RELAYED_REQUEST_ID_OFFSET | state.next_relayed_request_id;
+        state.next_relayed_request_id += 1;
+
+        state.relayed_requests.push(RelayedRequest {
+            request_id: Nat::from(request_id),
+            ..request.clone()
+        });
+
+        request_id
+    });
+
+    log!(
+        "[RELAY] request {} from chain {} to {} added as {}",
+        request.request_id,
+        request.source_chain_id,
+        request.target,
+        request_id
+    );
+
+    Ok(request_id)
+}
+
+/// Get the gas price, which is used for the AMA transactions.
+/// Called by the factory, so the cross-chain requests are charged at the gas price of this chain
+#[candid_method]
+#[update]
+pub async fn get_gas_price() -> NatResult {
+    validate_caller()?;
+
+    let w3 = web3::instance(get_metadata!(chain_rpc), get_metadata!(evm_rpc_canister))?;
+
+    Ok(gas_price(&w3).await?.to_nat())
+}
+
+/// Set the factory canister, which relays the cross-chain requests.
+/// Called by the factory after the instance is upgraded
+#[candid_method]
+#[update]
+pub fn set_apollo_canister(apollo_canister: Principal) -> Result<()> {
+    validate_caller()?;
+
+    STATE.with(|state| state.borrow_mut().apollo_canister = Some(apollo_canister.to_text()));
+
+    Ok(())
+}
@@ -1,4 +1,7 @@
 pub mod allowances;
 pub mod balances;
+pub mod callback_layouts;
 pub mod canister;
+pub mod coordinators;
 pub mod execution;
+pub mod subscriptions;
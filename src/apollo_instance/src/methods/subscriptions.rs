@@ -0,0 +1,99 @@
+use crate::{
+    types::{
+        allowances::Allowances,
+        subscriptions::{Subscription, SubscriptionRequest, Subscriptions},
+    },
+    Result,
+};
+use apollo_utils::{
+    address, canister::validate_caller, data_source::DataSource, errors::ApolloInstanceError,
+    get_metadata, log,
+};
+use candid::candid_method;
+use ic_cdk::{query, update};
+
+/// Subscribe the contract to the feed, which is delivered every `interval_sec` without on-chain requests
+///
+/// # Arguments
+///
+/// * `request` - Subscription, the contract must be granted to use the signer's balance, which pays for the deliveries
+/// * `msg` - SIWE message, For more information, refer to the [SIWE message specification](https://eips.ethereum.org/EIPS/eip-4361)
+/// * `sig` - SIWE signature, For more information, refer to the [SIWE message specification](https://eips.ethereum.org/EIPS/eip-4361)
+///
+/// # Returns
+///
+/// Returns the id of the subscription
+
+#[candid_method]
+#[update]
+pub async fn subscribe(request: SubscriptionRequest, msg: String, sig: String) -> Result<u64> {
+    let user = address::normalize(&apollo_utils::siwe::recover(msg, sig).await)?;
+
+    if Allowances::get_allowed_user(request.contract.clone())? != user {
+        return Err(ApolloInstanceError::ContractIsNotGranted(request.contract));
+    }
+
+    DataSource::from_feed_id(&request.feed_id, &get_metadata!(data_source_allowlist))
+        .map_err(|err| ApolloInstanceError::InvalidSubscription(err.to_string()))?;
+
+    let contract = request.contract.clone();
+    let id = Subscriptions::add(request)?;
+
+    log!("[SUBSCRIPTIONS] {user} subscribed {contract}, subscription id: {id}");
+    Ok(id)
+}
+
+/// Remove the subscription, signed by the user, who pays for the deliveries
+///
+/// # Arguments
+///
+/// * `id` - Id of the subscription
+/// * `msg` - SIWE message, For more information, refer to the [SIWE message specification](https://eips.ethereum.org/EIPS/eip-4361)
+/// * `sig` - SIWE signature, For more information, refer to the [SIWE message specification](https://eips.ethereum.org/EIPS/eip-4361)
+///
+/// # Returns
+///
+/// Returns a result that can contain an error message
+
+#[candid_method]
+#[update]
+pub async fn unsubscribe(id: u64, msg: String, sig: String) -> Result<()> {
+    let user = address::normalize(&apollo_utils::siwe::recover(msg, sig).await)?;
+    let subscription = Subscriptions::get(id)?;
+
+    if Allowances::get_allowed_user(subscription.contract.clone())? != user {
+        return Err(ApolloInstanceError::ContractIsNotGranted(
+            subscription.contract,
+        ));
+    }
+
+    Subscriptions::remove(id)?;
+
+    log!("[SUBSCRIPTIONS] {user} removed subscription {id}");
+    Ok(())
+}
+
+#[candid_method]
+#[update]
+pub fn unsubscribe_as_controller(id: u64) -> Result<()> {
+    validate_caller()?;
+
+    Subscriptions::remove(id)
+}
+
+#[candid_method]
+#[query]
+pub fn get_subscriptions(contract: Option<String>) -> Result<Vec<Subscription>> {
+    let contract = contract
+        .map(|contract| address::normalize(&contract))
+        .transpose()?;
+
+    Ok(Subscriptions::get_all()
+        .into_iter()
+        .filter(|subscription| {
+            contract
+                .as_ref()
+                .map_or(true, |contract| &subscription.contract == contract)
+        })
+        .collect())
+}
@@ -10,7 +10,7 @@ use ic_utils::{
 
 use crate::{
     jobs, memory,
-    types::{timer::Timer, State, STATE},
+    types::{coordinators::Coordinators, timer::Timer, State, STATE},
     utils::set_custom_panic_hook,
 };
 
@@ -26,6 +26,9 @@ async fn post_upgrade() {
     set_custom_panic_hook();
 
     load_upgrade_data();
+
+    Coordinators::migrate();
+
     if Timer::is_active() {
         Timer::set_timer(jobs::execute);
     }
@@ -1,9 +1,24 @@
-use apollo_utils::sybil::SybilAssetData;
+use apollo_utils::{
+    errors::{ApolloInstanceError, UtilsError},
+    get_metadata,
+    sybil::{AssetDataResult, SybilAssetData},
+    web3::sign_message_hash,
+};
 use candid::CandidType;
 use ic_cdk::api::management_canister::main::raw_rand;
-use ic_web3_rs::{ethabi::Token, types::U256};
+use ic_web3_rs::{
+    ethabi::{self, Token},
+    signing::keccak256,
+    types::U256,
+};
 use serde::{Deserialize, Serialize};
 
+use crate::utils::apollo_evm_address;
+
+use super::callback_layouts::CallbackLayout;
+
+const ETH_SIGNED_MESSAGE_PREFIX: &[u8] = b"\x19Ethereum Signed Message:\n32";
+
 #[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
 pub enum AssetData {
     DefaultPriceFeed {
@@ -12,6 +27,8 @@ pub enum AssetData {
         rate: u64,
         decimals: u64,
         timestamp: u64,
+        #[serde(default)]
+        signature: Option<String>,
     },
     CustomPriceFeed {
         request_id: u64,
@@ -19,33 +36,90 @@ pub enum AssetData {
         rate: u64,
         decimals: u64,
         timestamp: u64,
+        #[serde(default)]
+        signature: Option<String>,
     },
     CustomNumber {
         request_id: u64,
         id: String,
         value: u64,
         decimals: u64,
+        #[serde(default)]
+        signature: Option<String>,
     },
     CustomString {
         request_id: u64,
         id: String,
         value: String,
+        #[serde(default)]
+        signature: Option<String>,
     },
     Random {
         request_id: u64,
         num_words: u64,
     },
+    MultiFeed {
+        request_id: u64,
+        feeds: Vec<AssetData>,
+    },
 }
 
 impl AssetData {
-    pub async fn encode(&self) -> Vec<Token> {
+    pub async fn encode(&self) -> Result<Vec<Token>, ApolloInstanceError> {
         match self.clone() {
+            AssetData::Random {
+                request_id,
+                num_words,
+            } => {
+                let (entropy,) = raw_rand()
+                    .await
+                    .map_err(|(_, err)| ApolloInstanceError::FailedToGetRandomness(err))?;
+                let seed = derive_seed(&entropy, request_id);
+
+                let words = expand_seed(&seed, num_words)
+                    .into_iter()
+                    .map(Token::Uint)
+                    .collect();
+
+                let mut tokens = vec![Token::Uint(request_id.into()), Token::Array(words)];
+
+                if get_metadata!(sign_randomness) {
+                    let signature = sign_message_hash(
+                        randomness_message_hash(request_id, &seed),
+                        get_metadata!(key_name),
+                        &apollo_evm_address().await?,
+                    )
+                    .await?;
+
+                    tokens.push(Token::FixedBytes(seed.to_vec()));
+                    tokens.push(Token::Bytes(signature));
+                }
+
+                Ok(tokens)
+            }
+            // every feed is encoded the same way as in a single feed request
+            AssetData::MultiFeed { request_id, feeds } => {
+                let feeds = feeds
+                    .iter()
+                    .map(|feed| Ok(Token::Bytes(ethabi::encode(&feed.encode_feed()?))))
+                    .collect::<Result<Vec<_>, ApolloInstanceError>>()?;
+
+                Ok(vec![Token::Uint(request_id.into()), Token::Array(feeds)])
+            }
+            _ => self.encode_feed(),
+        }
+    }
+
+    /// Encodes the data of a single feed alongside its Sybil signature, if it is enabled
+    fn encode_feed(&self) -> Result<Vec<Token>, ApolloInstanceError> {
+        let mut tokens = match self.clone() {
             AssetData::DefaultPriceFeed {
                 request_id,
                 symbol,
                 rate,
                 decimals,
                 timestamp,
+                ..
             } => vec![
                 Token::Uint(request_id.into()),
                 Token::String(symbol.clone()),
@@ -59,6 +133,7 @@ impl AssetData {
                 rate,
                 decimals,
                 timestamp,
+                ..
             } => {
                 vec![
                     Token::Uint(request_id.into()),
@@ -73,6 +148,7 @@ impl AssetData {
                 id,
                 value,
                 decimals,
+                ..
             } => {
                 vec![
                     Token::Uint(request_id.into()),
@@ -85,6 +161,7 @@ impl AssetData {
                 request_id,
                 id,
                 value,
+                ..
             } => {
                 vec![
                     Token::Uint(request_id.into()),
@@ -92,23 +169,69 @@ impl AssetData {
                     Token::String(value.clone()),
                 ]
             }
-            AssetData::Random {
-                request_id,
-                num_words: num_of_random_bytes,
-            } => {
-                let mut vec = Vec::with_capacity(num_of_random_bytes as usize);
-                for _ in 0..num_of_random_bytes {
-                    let (random,) = raw_rand().await.expect("should be able to get random");
-                    vec.push(Token::Uint(U256::from_big_endian(&random)));
-                }
+            AssetData::Random { .. } | AssetData::MultiFeed { .. } => {
+                unreachable!("random and multi feeds are not single feeds")
+            }
+        };
+
+        if let Some(signature) = self.sybil_signature()? {
+            tokens.push(Token::Bytes(signature));
+        }
+
+        Ok(tokens)
+    }
+
+    /// Sybil signature of the data, if it should be delivered for this type of request.
+    /// Missing signature is delivered as empty bytes, so the callback layout stays the same
+    fn sybil_signature(&self) -> Result<Option<Vec<u8>>, ApolloInstanceError> {
+        let config = get_metadata!(sybil_signature);
 
-                vec![Token::Uint(request_id.into()), Token::Array(vec)]
+        let (signature, is_enabled) = match self {
+            AssetData::DefaultPriceFeed { signature, .. } => (signature, config.default_price_feed),
+            AssetData::CustomPriceFeed { signature, .. } => (signature, config.custom_price_feed),
+            AssetData::CustomNumber { signature, .. } => (signature, config.custom_number),
+            AssetData::CustomString { signature, .. } => (signature, config.custom_string),
+            AssetData::Random { .. } | AssetData::MultiFeed { .. } => return Ok(None),
+        };
+
+        if !is_enabled {
+            return Ok(None);
+        }
+
+        let signature = signature.as_deref().unwrap_or_default();
+
+        Ok(Some(
+            hex::decode(signature.trim_start_matches("0x"))
+                .map_err(|err| UtilsError::FromHexError(err.to_string()))?,
+        ))
+    }
+
+    /// Numeric value of the feed, which is tracked for the deviation of the subscriptions
+    pub fn value(&self) -> Option<u64> {
+        match self {
+            AssetData::DefaultPriceFeed { rate, .. } | AssetData::CustomPriceFeed { rate, .. } => {
+                Some(*rate)
             }
+            AssetData::CustomNumber { value, .. } => Some(*value),
+            _ => None,
         }
     }
 
-    pub fn from_sybil_asset_data_and_req_id(req_id: u64, sybil_asset_data: SybilAssetData) -> Self {
-        match sybil_asset_data {
+    /// Produces the calldata of the callback, which delivers the data to the requester's contract
+    pub async fn encode_call(
+        &self,
+        layout: &CallbackLayout,
+    ) -> Result<Vec<u8>, ApolloInstanceError> {
+        layout.encode(self.encode().await?)
+    }
+
+    pub fn from_sybil_asset_data_and_req_id(
+        req_id: u64,
+        sybil_asset_data: AssetDataResult,
+    ) -> Self {
+        let signature = sybil_asset_data.signature;
+
+        match sybil_asset_data.data {
             SybilAssetData::DefaultPriceFeed {
                 symbol,
                 rate,
@@ -120,6 +243,7 @@ impl AssetData {
                 rate,
                 decimals,
                 timestamp,
+                signature,
             },
             SybilAssetData::CustomPriceFeed {
                 symbol,
@@ -132,6 +256,7 @@ impl AssetData {
                 rate,
                 decimals,
                 timestamp,
+                signature,
             },
             SybilAssetData::CustomNumber {
                 id,
@@ -142,12 +267,75 @@ impl AssetData {
                 id,
                 value,
                 decimals,
+                signature,
             },
             SybilAssetData::CustomString { id, value } => AssetData::CustomString {
                 request_id: req_id,
                 id,
                 value,
+                signature,
             },
         }
     }
 }
+
+/// A single seed per request: `keccak256(raw_rand || request_id)`
+fn derive_seed(entropy: &[u8], request_id: u64) -> [u8; 32] {
+    let mut data = entropy.to_vec();
+    data.extend(ethabi::encode(&[Token::Uint(request_id.into())]));
+
+    keccak256(&data)
+}
+
+/// Words can be recomputed from the seed on-chain: `word_i = keccak256(abi.encode(seed, i))`
+fn expand_seed(seed: &[u8; 32], num_words: u64) -> Vec<U256> {
+    (0..num_words)
+        .map(|i| {
+            let data = ethabi::encode(&[Token::FixedBytes(seed.to_vec()), Token::Uint(i.into())]);
+
+            U256::from_big_endian(&keccak256(&data))
+        })
+        .collect()
+}
+
+/// Hash, which is signed by the AMA, consumers verify it with
+/// `ecrecover(toEthSignedMessageHash(keccak256(abi.encodePacked(requestId, seed))), v, r, s)`
+fn randomness_message_hash(request_id: u64, seed: &[u8; 32]) -> [u8; 32] {
+    let mut message = ethabi::encode(&[Token::Uint(request_id.into())]);
+    message.extend_from_slice(seed);
+
+    let mut prefixed = ETH_SIGNED_MESSAGE_PREFIX.to_vec();
+    prefixed.extend(keccak256(&message));
+
+    keccak256(&prefixed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_seed() {
+        let entropy = [7u8; 32];
+
+        assert_eq!(derive_seed(&entropy, 1), derive_seed(&entropy, 1));
+        assert_ne!(derive_seed(&entropy, 1), derive_seed(&entropy, 2));
+    }
+
+    #[test]
+    fn test_expand_seed() {
+        let seed = derive_seed(&[7u8; 32], 1);
+        let words = expand_seed(&seed, 3);
+
+        assert_eq!(words.len(), 3);
+        assert_ne!(words[0], words[1]);
+        assert_ne!(words[1], words[2]);
+
+        let mut data = seed.to_vec();
+        data.extend([0u8; 31]);
+        data.push(2);
+        assert_eq!(words[2], U256::from_big_endian(&keccak256(&data)));
+
+        assert!(expand_seed(&seed, 0).is_empty());
+    }
+}
@@ -111,6 +111,18 @@ impl Balances {
                 .unwrap_or_default())
         })
     }
+
+    /// Sum of the user balances, which the AMA should be able to cover
+    pub fn total() -> Nat {
+        STATE.with(|state| {
+            let state = state.borrow();
+            let inner = state.balances.0.borrow();
+
+            inner
+                .iter()
+                .fold(Nat::from(0), |total, (_, balance)| total + balance.0.amount)
+        })
+    }
 }
 
 #[cfg(test)]
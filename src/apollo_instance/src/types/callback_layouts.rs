@@ -0,0 +1,192 @@
+use std::borrow::{Borrow, BorrowMut};
+
+use apollo_utils::{address, errors::ApolloInstanceError, memory::Cbor};
+use candid::CandidType;
+use ic_stable_structures::StableBTreeMap;
+use ic_web3_rs::ethabi::{self, param_type::Reader, ParamType, Token};
+use serde::{Deserialize, Serialize};
+
+use crate::{log, memory::VMemory};
+
+use super::STATE;
+
+pub const DEFAULT_CALLBACK_SIGNATURE: &str = "rawFulfillData(bytes)";
+
+/// Function of the requester's contract, which receives the requested data.
+///
+/// If the function takes a single `bytes` argument, the data is passed ABI-encoded,
+/// otherwise the data fields are passed as separate arguments, e.g.
+/// `fulfillPrice(uint256,string,uint256,uint256,uint256)` for price feeds
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct CallbackLayout {
+    /// Canonical signature of the function, `name(type1,type2,...)`
+    pub signature: String,
+}
+
+impl Default for CallbackLayout {
+    fn default() -> Self {
+        Self {
+            signature: DEFAULT_CALLBACK_SIGNATURE.to_string(),
+        }
+    }
+}
+
+impl CallbackLayout {
+    pub fn new(signature: &str) -> Result<Self, ApolloInstanceError> {
+        let (name, params) = parse_signature(signature)?;
+
+        Ok(Self {
+            signature: format!(
+                "{}({})",
+                name,
+                params
+                    .iter()
+                    .map(ParamType::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        })
+    }
+
+    /// Produces the calldata of the callback from the encoded data fields
+    pub fn encode(&self, tokens: Vec<Token>) -> Result<Vec<u8>, ApolloInstanceError> {
+        let (name, params) = parse_signature(&self.signature)?;
+
+        let tokens = if params == [ParamType::Bytes] {
+            vec![Token::Bytes(ethabi::encode(&tokens))]
+        } else {
+            tokens
+        };
+
+        if !Token::types_check(&tokens, &params) {
+            return Err(ApolloInstanceError::CallbackDataMismatch(
+                self.signature.clone(),
+            ));
+        }
+
+        let mut call_data = ethabi::short_signature(&name, &params).to_vec();
+        call_data.extend(ethabi::encode(&tokens));
+
+        Ok(call_data)
+    }
+}
+
+fn parse_signature(signature: &str) -> Result<(String, Vec<ParamType>), ApolloInstanceError> {
+    let invalid = || ApolloInstanceError::InvalidCallbackSignature(signature.to_string());
+
+    let signature = signature.replace(' ', "");
+    let (name, params) = signature.split_once('(').ok_or_else(invalid)?;
+
+    if name.is_empty()
+        || name.starts_with(|c: char| c.is_ascii_digit())
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(invalid());
+    }
+
+    // params are read as a tuple, so nested tuples and arrays are handled by the ethabi reader
+    match Reader::read(&format!("({params}")) {
+        Ok(ParamType::Tuple(params)) if !params.is_empty() => Ok((name.to_string(), params)),
+        _ => Err(invalid()),
+    }
+}
+
+/// contract address => callback layout, contracts without a layout use the default one
+pub struct CallbackLayouts(StableBTreeMap<String, Cbor<CallbackLayout>, VMemory>);
+
+impl Default for CallbackLayouts {
+    fn default() -> Self {
+        Self(StableBTreeMap::init(
+            crate::memory::get_callback_layouts_memory(),
+        ))
+    }
+}
+
+impl CallbackLayouts {
+    pub fn set(contract: &str, layout: Option<CallbackLayout>) -> Result<(), ApolloInstanceError> {
+        let contract = address::normalize(contract)?;
+
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            let inner = state.callback_layouts.0.borrow_mut();
+
+            match &layout {
+                Some(layout) => inner.insert(contract.clone(), Cbor(layout.clone())),
+                None => inner.remove(&contract),
+            };
+        });
+
+        log!(
+            "[CALLBACK LAYOUTS] contract = {}, layout = {:?}",
+            contract,
+            layout
+        );
+
+        Ok(())
+    }
+
+    pub fn get(contract: &str) -> Result<CallbackLayout, ApolloInstanceError> {
+        let contract = address::normalize(contract)?;
+
+        STATE.with(|state| {
+            let state = state.borrow();
+            let inner = state.callback_layouts.0.borrow();
+
+            Ok(inner
+                .get(&contract)
+                .map(|layout| layout.0)
+                .unwrap_or_default())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use ic_web3_rs::{ethabi::Function, types::U256};
+
+    use super::*;
+
+    const TARGET_FUNCTION_ABI: &str = include_str!("../../../../assets/TargetFunctionABI.json");
+
+    #[test]
+    fn test_target_function_abi() -> Result<()> {
+        let target_function = serde_json::from_str::<Function>(TARGET_FUNCTION_ABI)?;
+        assert_eq!(target_function.signature(), DEFAULT_CALLBACK_SIGNATURE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_callback_layout_new() {
+        let layout = CallbackLayout::new("fulfill(uint, string, uint256[])").unwrap();
+        assert_eq!(layout.signature, "fulfill(uint256,string,uint256[])");
+
+        assert!(CallbackLayout::new("fulfill").is_err());
+        assert!(CallbackLayout::new("fulfill()").is_err());
+        assert!(CallbackLayout::new("ful-fill(uint256)").is_err());
+        assert!(CallbackLayout::new("fulfill(price)").is_err());
+    }
+
+    #[test]
+    fn test_callback_layout_encode() {
+        let tokens = vec![Token::Uint(U256::from(1)), Token::String("ETH/USD".into())];
+
+        let default = CallbackLayout::default().encode(tokens.clone()).unwrap();
+        assert_eq!(
+            default[4..],
+            ethabi::encode(&[Token::Bytes(ethabi::encode(&tokens))])
+        );
+
+        let typed = CallbackLayout::new("fulfill(uint256,string)").unwrap();
+        let call_data = typed.encode(tokens.clone()).unwrap();
+        assert_eq!(
+            call_data[..4],
+            ethabi::short_signature("fulfill", &[ParamType::Uint(256), ParamType::String])
+        );
+        assert_eq!(call_data[4..], ethabi::encode(&tokens));
+
+        let mismatched = CallbackLayout::new("fulfill(uint256,uint256)").unwrap();
+        assert!(mismatched.encode(tokens).is_err());
+    }
+}
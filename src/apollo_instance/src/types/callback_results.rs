@@ -0,0 +1,69 @@
+use std::borrow::{Borrow, BorrowMut};
+
+use apollo_utils::memory::Cbor;
+use candid::{CandidType, Nat};
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{log, memory::VMemory};
+
+use super::STATE;
+
+#[derive(Clone, Debug, Default, CandidType, Serialize, Deserialize)]
+pub struct CallbackResult {
+    pub request_id: u64,
+    pub requester: String,
+    pub success: bool,
+    pub used_gas: Nat,
+    /// Amount reduced from the requester's balance, gas is charged even if the callback reverted
+    pub charged: Nat,
+    pub revert_reason: Option<String>,
+    pub tx_hash: String,
+    pub executed_at: u64,
+    /// Native token price, which converted the USD fee, `None` if the fee is in the native token
+    #[serde(default)]
+    pub fee_rate: Option<FeeConversionRate>,
+    /// Cycles, which the instance spent on the request, a part of them is shared by the requests of the tick
+    #[serde(default)]
+    pub cycles_spent: Nat,
+}
+
+/// request_id => result of the callback execution
+pub struct CallbackResults(StableBTreeMap<u64, Cbor<CallbackResult>, VMemory>);
+
+impl Default for CallbackResults {
+    fn default() -> Self {
+        Self(StableBTreeMap::init(
+            crate::memory::get_callback_results_memory(),
+        ))
+    }
+}
+
+impl CallbackResults {
+    pub fn add(result: CallbackResult) {
+        log!(
+            "[CALLBACK RESULTS] request_id = {}, success = {}, used gas = {}, charged = {}, revert reason = {:?}",
+            result.request_id,
+            result.success,
+            result.used_gas,
+            result.charged,
+            result.revert_reason
+        );
+
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            let inner = state.callback_results.0.borrow_mut();
+
+            inner.insert(result.request_id, Cbor(result));
+        });
+    }
+
+    pub fn get(request_id: u64) -> Option<CallbackResult> {
+        STATE.with(|state| {
+            let state = state.borrow();
+            let inner = state.callback_results.0.borrow();
+
+            inner.get(&request_id).map(|result| result.0)
+        })
+    }
+}
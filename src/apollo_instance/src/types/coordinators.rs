@@ -0,0 +1,151 @@
+use std::borrow::{Borrow, BorrowMut};
+
+use apollo_utils::{
+    address, apollo_instance::CoordinatorAbiVersion, errors::ApolloInstanceError, memory::Cbor,
+};
+use candid::CandidType;
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{log, memory::VMemory};
+
+use super::STATE;
+
+/// Coordinator contract, which is watched for the requests
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct Coordinator {
+    pub address: String,
+    pub abi_version: CoordinatorAbiVersion,
+    /// Last block, from which the logs were parsed, `None` to start from the latest block
+    pub last_parsed_block: Option<u64>,
+}
+
+/// coordinator address => coordinator
+pub struct Coordinators(StableBTreeMap<String, Cbor<Coordinator>, VMemory>);
+
+impl Default for Coordinators {
+    fn default() -> Self {
+        Self(StableBTreeMap::init(
+            crate::memory::get_coordinators_memory(),
+        ))
+    }
+}
+
+impl Coordinators {
+    pub fn add(
+        address: &str,
+        abi_version: CoordinatorAbiVersion,
+        last_parsed_block: Option<u64>,
+    ) -> Result<(), ApolloInstanceError> {
+        let address = address::normalize(address)?;
+
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            let inner = state.coordinators.0.borrow_mut();
+
+            if inner.contains_key(&address) {
+                return Err(ApolloInstanceError::CoordinatorAlreadyExists(
+                    address.clone(),
+                ));
+            }
+
+            inner.insert(
+                address.clone(),
+                Cbor(Coordinator {
+                    address: address.clone(),
+                    abi_version,
+                    last_parsed_block,
+                }),
+            );
+
+            Ok(())
+        })?;
+
+        log!(
+            "[COORDINATORS] added {}, abi version: {:?}, from block: {:?}",
+            address,
+            abi_version,
+            last_parsed_block
+        );
+
+        Ok(())
+    }
+
+    pub fn remove(address: &str) -> Result<(), ApolloInstanceError> {
+        let address = address::normalize(address)?;
+
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            let inner = state.coordinators.0.borrow_mut();
+
+            inner
+                .remove(&address)
+                .ok_or_else(|| ApolloInstanceError::CoordinatorNotFound(address.clone()))
+        })?;
+
+        log!("[COORDINATORS] removed {}", address);
+
+        Ok(())
+    }
+
+    pub fn get_all() -> Vec<Coordinator> {
+        STATE.with(|state| {
+            let state = state.borrow();
+            let inner = state.coordinators.0.borrow();
+
+            inner.iter().map(|(_, coordinator)| coordinator.0).collect()
+        })
+    }
+
+    /// Updates the cursor of the coordinator, `None` updates all the coordinators
+    pub fn set_last_parsed_block(address: Option<&str>, last_parsed_block: Option<u64>) {
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            let inner = state.coordinators.0.borrow_mut();
+
+            let coordinators: Vec<Coordinator> = match address {
+                Some(address) => inner
+                    .get(&address.to_string())
+                    .into_iter()
+                    .map(|c| c.0)
+                    .collect(),
+                None => inner.iter().map(|(_, coordinator)| coordinator.0).collect(),
+            };
+
+            for mut coordinator in coordinators {
+                coordinator.last_parsed_block = last_parsed_block;
+                inner.insert(coordinator.address.clone(), Cbor(coordinator));
+            }
+        });
+    }
+
+    /// Instances, created before multiple coordinators were supported,
+    /// watch the metadata coordinator from their last parsed block
+    pub fn migrate() {
+        let is_empty = STATE.with(|state| state.borrow().coordinators.0.is_empty());
+        let (apollo_coordinator, last_parsed_block) = STATE.with(|state| {
+            let state = state.borrow();
+
+            (
+                state.metadata.get().0.apollo_coordinator.clone(),
+                state.last_parsed_logs_from_block,
+            )
+        });
+
+        if !is_empty || apollo_coordinator.is_empty() {
+            return;
+        }
+
+        if let Err(err) = Self::add(
+            &apollo_coordinator,
+            CoordinatorAbiVersion::V1,
+            last_parsed_block,
+        ) {
+            log!(
+                "[COORDINATORS] unable to migrate {}: {}",
+                apollo_coordinator,
+                err
+            );
+        }
+    }
+}
@@ -1,7 +1,15 @@
 use std::cell::RefCell;
 
 use crate::memory::VMemory;
-use apollo_utils::{apollo_instance::ApolloInstanceMetadata, memory::Cbor};
+use apollo_utils::{
+    address,
+    apollo_instance::{
+        ApolloInstanceMetadata, ApolloInstanceStats, RelayedRequest, RequestKind, SolvencyStatus,
+    },
+    errors::UtilsError,
+    memory::Cbor,
+    nat::ToNativeTypes,
+};
 use ic_stable_structures::StableCell;
 use ic_web3_rs::{
     ethabi::Log,
@@ -9,12 +17,25 @@ use ic_web3_rs::{
 };
 use serde::{Deserialize, Serialize};
 
-use self::{allowances::Allowances, balances::Balances, timer::Timer, withdraw::WithdrawRequests};
+use self::{
+    allowances::Allowances, balances::Balances, callback_layouts::CallbackLayouts,
+    callback_results::CallbackResults, coordinators::Coordinators, nonce_manager::NonceManager,
+    request_volumes::RequestVolumes, stats::TickCycles, subscriptions::Subscriptions, timer::Timer,
+    unknown_events::UnknownEvents, withdraw::WithdrawRequests,
+};
 
 pub mod allowances;
 pub mod asset_data;
 pub mod balances;
+pub mod callback_layouts;
+pub mod callback_results;
+pub mod coordinators;
+pub mod nonce_manager;
+pub mod request_volumes;
+pub mod stats;
+pub mod subscriptions;
 pub mod timer;
+pub mod unknown_events;
 pub mod withdraw;
 
 #[derive(Serialize, Deserialize)]
@@ -31,13 +52,59 @@ pub struct State {
     #[serde(skip)]
     pub allowances: Allowances,
 
+    #[serde(skip)]
+    pub nonce_manager: NonceManager,
+
+    #[serde(skip)]
+    pub callback_results: CallbackResults,
+
+    #[serde(skip)]
+    pub callback_layouts: CallbackLayouts,
+
+    #[serde(skip)]
+    pub unknown_events: UnknownEvents,
+
+    #[serde(skip)]
+    pub subscriptions: Subscriptions,
+
+    #[serde(skip)]
+    pub coordinators: Coordinators,
+
+    #[serde(skip)]
+    pub request_volumes: RequestVolumes,
+
+    // the factory canister, which relays the cross-chain requests
+    #[serde(default)]
+    pub apollo_canister: Option<String>,
+    // requests from other chains, which are delivered on the next tick
+    #[serde(default)]
+    pub relayed_requests: Vec<RelayedRequest>,
+    #[serde(default)]
+    pub stats: ApolloInstanceStats,
+    // the last AMA solvency check, the fulfillment is paused while the AMA is insolvent
+    #[serde(default)]
+    pub solvency: Option<SolvencyStatus>,
+    // cycles of the last ticks, the oldest one is the first
+    #[serde(default)]
+    pub tick_cycles: Vec<TickCycles>,
+    // removed subscription ids are not reused, so late results are not attributed to a new subscription
+    #[serde(default)]
+    pub next_subscription_id: u64,
+    // relayed requests get own ids, so they don't collide with the local requests and the requests of other chains
+    #[serde(default)]
+    pub next_relayed_request_id: u64,
+
     // Frequency in seconds to check apollo coordinator for new requests
     pub timer_frequency_sec: u64,
     pub timer: Timer,
-    // last parsed block for logs_polling
+    // last parsed block of the metadata coordinator, the cursors are stored in `Coordinators` now
     pub last_parsed_logs_from_block: Option<u64>,
 }
 
+/// Relayed requests are delivered under `RELAYED_REQUEST_ID_OFFSET | n`,
+/// below the subscription ids, so their callback results don't overwrite the local ones
+pub const RELAYED_REQUEST_ID_OFFSET: u64 = 1 << 62;
+
 thread_local! {
     pub static STATE: RefCell<State> = RefCell::new(State::default());
 }
@@ -54,6 +121,20 @@ impl Default for State {
             balances: Balances::default(),
             withdraw_requests: WithdrawRequests::default(),
             allowances: Allowances::default(),
+            nonce_manager: NonceManager::default(),
+            callback_results: CallbackResults::default(),
+            callback_layouts: CallbackLayouts::default(),
+            unknown_events: UnknownEvents::default(),
+            subscriptions: Subscriptions::default(),
+            coordinators: Coordinators::default(),
+            request_volumes: RequestVolumes::default(),
+            apollo_canister: None,
+            relayed_requests: vec![],
+            stats: ApolloInstanceStats::default(),
+            solvency: None,
+            tick_cycles: vec![],
+            next_subscription_id: 0,
+            next_relayed_request_id: 0,
             timer_frequency_sec: 0,
             timer: Timer::default(),
             last_parsed_logs_from_block: None,
@@ -75,6 +156,28 @@ pub enum ApolloCoordinatorRequest {
         num_words: U256,
         requester: H160,
     },
+    MultiFeed {
+        request_id: U256,
+        feed_ids: Vec<String>,
+        callback_gas_limit: U256,
+        requester: H160,
+    },
+    /// Data feed, which is delivered to the `target` contract on the target chain
+    CrossChainDataFeed {
+        request_id: U256,
+        feed_id: String,
+        callback_gas_limit: U256,
+        requester: H160,
+        target_chain_id: U256,
+        target: H160,
+    },
+    /// Data feed, which was requested on another chain and is delivered to the `requester` on this chain
+    RelayedDataFeed {
+        request_id: U256,
+        feed_id: String,
+        callback_gas_limit: U256,
+        requester: H160,
+    },
 }
 
 impl ApolloCoordinatorRequest {
@@ -82,6 +185,29 @@ impl ApolloCoordinatorRequest {
         match self {
             Self::DataFeed { feed_id, .. } => feed_id.clone(),
             Self::RandomFeed { .. } => "random".to_string(),
+            Self::MultiFeed { feed_ids, .. } => feed_ids.join(","),
+            Self::CrossChainDataFeed { feed_id, .. } | Self::RelayedDataFeed { feed_id, .. } => {
+                feed_id.clone()
+            }
+        }
+    }
+
+    /// Feeds, which should be fetched to fulfill the request
+    pub fn feed_ids(&self) -> Vec<String> {
+        match self {
+            Self::DataFeed { feed_id, .. } => vec![feed_id.clone()],
+            Self::RandomFeed { .. } => vec![],
+            Self::MultiFeed { feed_ids, .. } => feed_ids.clone(),
+            // fetched by the target chain instance
+            Self::CrossChainDataFeed { .. } => vec![],
+            Self::RelayedDataFeed { feed_id, .. } => vec![feed_id.clone()],
+        }
+    }
+
+    pub fn kind(&self) -> RequestKind {
+        match self {
+            Self::RandomFeed { .. } => RequestKind::Random,
+            _ => RequestKind::DataFeed,
         }
     }
 
@@ -93,6 +219,25 @@ impl ApolloCoordinatorRequest {
             Self::RandomFeed {
                 callback_gas_limit, ..
             } => callback_gas_limit.clone(),
+            Self::MultiFeed {
+                callback_gas_limit, ..
+            } => callback_gas_limit.clone(),
+            Self::CrossChainDataFeed {
+                callback_gas_limit, ..
+            } => callback_gas_limit.clone(),
+            Self::RelayedDataFeed {
+                callback_gas_limit, ..
+            } => callback_gas_limit.clone(),
+        }
+    }
+
+    pub fn request_id(&self) -> U256 {
+        match self {
+            Self::DataFeed { request_id, .. } => *request_id,
+            Self::RandomFeed { request_id, .. } => *request_id,
+            Self::MultiFeed { request_id, .. } => *request_id,
+            Self::CrossChainDataFeed { request_id, .. } => *request_id,
+            Self::RelayedDataFeed { request_id, .. } => *request_id,
         }
     }
 
@@ -100,9 +245,21 @@ impl ApolloCoordinatorRequest {
         match self {
             Self::DataFeed { requester, .. } => requester.clone(),
             Self::RandomFeed { requester, .. } => requester.clone(),
+            Self::MultiFeed { requester, .. } => requester.clone(),
+            Self::CrossChainDataFeed { requester, .. } => requester.clone(),
+            Self::RelayedDataFeed { requester, .. } => requester.clone(),
         }
     }
 
+    pub fn from_relayed_request(request: RelayedRequest) -> Result<Self, UtilsError> {
+        Ok(Self::RelayedDataFeed {
+            request_id: request.request_id.to_u256(),
+            feed_id: request.feed_id,
+            callback_gas_limit: request.callback_gas_limit.to_u256(),
+            requester: address::to_h160(&request.target)?,
+        })
+    }
+
     pub fn new_from_data_feed_log(log: Log) -> Self {
         let params = log.params;
 
@@ -188,4 +345,115 @@ impl ApolloCoordinatorRequest {
             requester,
         }
     }
+
+    pub fn new_from_multi_feed_log(log: Log) -> Self {
+        let params = log.params;
+
+        let request_id = params
+            .get(0)
+            .expect("should be able to get request_id from log")
+            .value
+            .clone()
+            .into_uint()
+            .expect("should be able to convert to uint");
+
+        let feed_ids = params
+            .get(1)
+            .expect("should be able to get feed_ids from log")
+            .value
+            .clone()
+            .into_array()
+            .expect("should be able to convert to array")
+            .into_iter()
+            .map(|feed_id| {
+                feed_id
+                    .into_string()
+                    .expect("should be able to convert to string")
+            })
+            .collect();
+
+        let callback_gas_limit = params
+            .get(2)
+            .expect("should be able to get callback_gas_limit from log")
+            .value
+            .clone()
+            .into_uint()
+            .expect("should be able to convert to uint");
+
+        let requester = params
+            .get(3)
+            .expect("should be able to get requester from log")
+            .value
+            .clone()
+            .into_address()
+            .expect("should be able to convert to address");
+
+        Self::MultiFeed {
+            request_id,
+            feed_ids,
+            callback_gas_limit,
+            requester,
+        }
+    }
+
+    pub fn new_from_cross_chain_data_feed_log(log: Log) -> Self {
+        let params = log.params;
+
+        let request_id = params
+            .get(0)
+            .expect("should be able to get request_id from log")
+            .value
+            .clone()
+            .into_uint()
+            .expect("should be able to convert to uint");
+
+        let feed_id = params
+            .get(1)
+            .expect("should be able to get feed_id from log")
+            .value
+            .clone()
+            .into_string()
+            .expect("should be able to convert to string");
+
+        let callback_gas_limit = params
+            .get(2)
+            .expect("should be able to get callback_gas_limit from log")
+            .value
+            .clone()
+            .into_uint()
+            .expect("should be able to convert to uint");
+
+        let requester = params
+            .get(3)
+            .expect("should be able to get requester from log")
+            .value
+            .clone()
+            .into_address()
+            .expect("should be able to convert to address");
+
+        let target_chain_id = params
+            .get(4)
+            .expect("should be able to get target_chain_id from log")
+            .value
+            .clone()
+            .into_uint()
+            .expect("should be able to convert to uint");
+
+        let target = params
+            .get(5)
+            .expect("should be able to get target from log")
+            .value
+            .clone()
+            .into_address()
+            .expect("should be able to convert to address");
+
+        Self::CrossChainDataFeed {
+            request_id,
+            feed_id,
+            callback_gas_limit,
+            requester,
+            target_chain_id,
+            target,
+        }
+    }
 }
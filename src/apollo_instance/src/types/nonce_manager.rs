@@ -0,0 +1,263 @@
+use std::{
+    borrow::{Borrow, BorrowMut},
+    collections::HashMap,
+};
+
+use apollo_utils::{
+    address, apollo_instance::FeeConversionRate, memory::Cbor, multicall::Call, nat::ToNatType,
+    time, web3::SentTransaction,
+};
+use candid::{CandidType, Nat};
+use ic_stable_structures::StableBTreeMap;
+use ic_web3_rs::types::U256;
+use serde::{Deserialize, Serialize};
+
+use crate::{log, memory::VMemory};
+
+use super::STATE;
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct PendingCall {
+    #[serde(default)]
+    pub request_id: u64,
+    pub target: String,
+    pub gas_limit: Nat,
+    #[serde(default)]
+    pub prepaid: bool,
+    /// `None` for the calls, which were sent before the fee schedule, they are charged `apollos_fee`
+    #[serde(default)]
+    pub fee: Option<Nat>,
+    /// Surcharge for the attached cycles, it is fixed at send time
+    #[serde(default)]
+    pub surcharge: Nat,
+    /// Amount, which was deducted from the requester's balance at send time, the unused part is refunded.
+    /// 0 for the calls, which were sent before the reservations, they are charged on settlement
+    #[serde(default)]
+    pub reserved: Nat,
+    /// Cycles, which the instance spent on the request, they are reported, but not billed
+    #[serde(default)]
+    pub cycles: Nat,
+    /// Delivered value of the subscription, the subscription is marked as delivered once the tx is mined
+    #[serde(default)]
+    pub subscription_value: Option<u64>,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct PendingTransfer {
+    pub from: String,
+    pub receiver: String,
+    pub amount: Nat,
+}
+
+/// What should be done once the transaction is mined
+#[derive(Clone, Debug, Default, CandidType, Serialize, Deserialize)]
+pub enum TxPurpose {
+    #[default]
+    Unknown,
+    /// Requesters are charged for the used gas
+    Multicall {
+        calls: Vec<PendingCall>,
+        gas_price: Nat,
+        #[serde(default)]
+        fee_rate: Option<FeeConversionRate>,
+    },
+    /// Balances are reduced on submission and refunded if the transaction fails
+    Multitransfer { transfers: Vec<PendingTransfer> },
+}
+
+impl TxPurpose {
+    /// # Arguments
+    ///
+    /// * `subscription_values` - request id => delivered value of the subscription
+    pub fn multicall(
+        calls: &[Call],
+        gas_price: U256,
+        fee_rate: Option<FeeConversionRate>,
+        subscription_values: &HashMap<u64, Option<u64>>,
+    ) -> Self {
+        Self::Multicall {
+            calls: calls
+                .iter()
+                .map(|call| PendingCall {
+                    request_id: call.request_id,
+                    target: address::from_h160(&call.target),
+                    gas_limit: call.gas_limit.to_nat(),
+                    prepaid: call.prepaid,
+                    fee: Some(call.fee.to_nat()),
+                    surcharge: call.surcharge.to_nat(),
+                    reserved: call.reserved.to_nat(),
+                    cycles: Nat::from(call.cycles),
+                    subscription_value: subscription_values
+                        .get(&call.request_id)
+                        .copied()
+                        .flatten(),
+                })
+                .collect(),
+            gas_price: gas_price.to_nat(),
+            fee_rate,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, CandidType, Serialize, Deserialize)]
+pub struct InFlightTx {
+    pub nonce: u64,
+    /// Hashes of the transaction submissions, the last one is the most recent
+    pub tx_hashes: Vec<String>,
+    /// Hashes of the zero-value self-transfers, which replace the transaction
+    #[serde(default)]
+    pub cancel_tx_hashes: Vec<String>,
+    pub to: String,
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+    pub value: Nat,
+    pub gas: Nat,
+    pub gas_price: Nat,
+    pub first_sent_at: u64,
+    pub last_sent_at: u64,
+    pub speed_ups: u32,
+    #[serde(default)]
+    pub purpose: TxPurpose,
+}
+
+impl InFlightTx {
+    fn new(tx: SentTransaction, purpose: TxPurpose) -> Self {
+        let now = time::in_seconds();
+
+        Self {
+            nonce: tx.nonce.as_u64(),
+            tx_hashes: vec![format!("{:?}", tx.tx_hash)],
+            cancel_tx_hashes: vec![],
+            to: address::from_h160(&tx.to),
+            data: tx.data,
+            value: tx.value.to_nat(),
+            gas: tx.gas.to_nat(),
+            gas_price: tx.gas_price.to_nat(),
+            first_sent_at: now,
+            last_sent_at: now,
+            speed_ups: 0,
+            purpose,
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        !self.cancel_tx_hashes.is_empty()
+    }
+}
+
+/// nonce => transaction sent from the AMA, which is not processed yet
+pub struct NonceManager(StableBTreeMap<u64, Cbor<InFlightTx>, VMemory>);
+
+impl Default for NonceManager {
+    fn default() -> Self {
+        Self(StableBTreeMap::init(
+            crate::memory::get_in_flight_txs_memory(),
+        ))
+    }
+}
+
+impl NonceManager {
+    /// Returns the nonce for the next AMA transaction, taking into account
+    /// transactions which were sent, but are not mined yet
+    pub fn next_nonce(chain_nonce: U256) -> U256 {
+        let next_tracked_nonce = STATE.with(|state| {
+            state
+                .borrow()
+                .nonce_manager
+                .0
+                .last_key_value()
+                .map(|(nonce, _)| U256::from(nonce + 1))
+        });
+
+        match next_tracked_nonce {
+            Some(nonce) if nonce > chain_nonce => nonce,
+            _ => chain_nonce,
+        }
+    }
+
+    pub fn track(tx: SentTransaction, purpose: TxPurpose) {
+        let in_flight_tx = InFlightTx::new(tx, purpose);
+
+        log!(
+            "[NONCE MANAGER] Tracking tx: nonce = {}, hash = {}",
+            in_flight_tx.nonce,
+            in_flight_tx.tx_hashes[0]
+        );
+
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            let inner = state.nonce_manager.0.borrow_mut();
+
+            inner.insert(in_flight_tx.nonce, Cbor(in_flight_tx));
+        });
+    }
+
+    /// Records the replacement of the tracked transaction, sent with the same nonce
+    pub fn track_replacement(tx: SentTransaction, is_cancel: bool) {
+        let nonce = tx.nonce.as_u64();
+        let tx_hash = format!("{:?}", tx.tx_hash);
+
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            let inner = state.nonce_manager.0.borrow_mut();
+
+            let mut in_flight_tx = match inner.get(&nonce) {
+                Some(in_flight_tx) => in_flight_tx,
+                None => Cbor(InFlightTx::new(tx.clone(), TxPurpose::Unknown)),
+            };
+
+            if is_cancel {
+                // the first cancellation starts a new round of speed ups
+                in_flight_tx.speed_ups = if in_flight_tx.is_cancelled() {
+                    in_flight_tx.speed_ups + 1
+                } else {
+                    0
+                };
+                in_flight_tx.cancel_tx_hashes.push(tx_hash.clone());
+            } else {
+                in_flight_tx.tx_hashes.push(tx_hash.clone());
+                in_flight_tx.speed_ups += 1;
+            }
+
+            in_flight_tx.gas_price = tx.gas_price.to_nat();
+            in_flight_tx.last_sent_at = time::in_seconds();
+
+            inner.insert(nonce, in_flight_tx);
+        });
+
+        log!(
+            "[NONCE MANAGER] Tx replaced: nonce = {}, hash = {}, cancel = {}",
+            nonce,
+            tx_hash,
+            is_cancel
+        );
+    }
+
+    pub fn remove(nonce: u64) {
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            let inner = state.nonce_manager.0.borrow_mut();
+
+            inner.remove(&nonce);
+        });
+    }
+
+    /// Whether the call of the request is in a multicall transaction, which is not mined yet
+    pub fn has_pending_call(request_id: u64) -> bool {
+        Self::get_all().iter().any(|tx| match &tx.purpose {
+            TxPurpose::Multicall { calls, .. } => {
+                calls.iter().any(|call| call.request_id == request_id)
+            }
+            _ => false,
+        })
+    }
+
+    pub fn get_all() -> Vec<InFlightTx> {
+        STATE.with(|state| {
+            let state = state.borrow();
+            let inner = state.nonce_manager.0.borrow();
+
+            inner.iter().map(|(_, tx)| tx.0).collect()
+        })
+    }
+}
@@ -0,0 +1,40 @@
+use std::borrow::{Borrow, BorrowMut};
+
+use ic_stable_structures::StableBTreeMap;
+
+use crate::memory::VMemory;
+
+use super::STATE;
+
+/// requester => number of the callbacks, which the requester was charged for.
+/// Used for the volume discounts of the fee schedule
+pub struct RequestVolumes(StableBTreeMap<String, u64, VMemory>);
+
+impl Default for RequestVolumes {
+    fn default() -> Self {
+        Self(StableBTreeMap::init(
+            crate::memory::get_request_volumes_memory(),
+        ))
+    }
+}
+
+impl RequestVolumes {
+    pub fn get(requester: &str) -> u64 {
+        STATE.with(|state| {
+            let state = state.borrow();
+            let inner = state.request_volumes.0.borrow();
+
+            inner.get(&requester.to_string()).unwrap_or_default()
+        })
+    }
+
+    pub fn increment(requester: &str) {
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            let inner = state.request_volumes.0.borrow_mut();
+
+            let requests = inner.get(&requester.to_string()).unwrap_or_default();
+            inner.insert(requester.to_string(), requests + 1);
+        });
+    }
+}
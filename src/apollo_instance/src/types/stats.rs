@@ -0,0 +1,56 @@
+use apollo_utils::apollo_instance::ApolloInstanceStats;
+use candid::{CandidType, Nat};
+use serde::{Deserialize, Serialize};
+
+use super::STATE;
+
+const MAX_TICK_CYCLES: usize = 100;
+
+/// Cycles, which were spent by a single tick
+#[derive(Clone, Debug, Default, CandidType, Serialize, Deserialize)]
+pub struct TickCycles {
+    pub started_at: u64,
+    pub cycles_spent: Nat,
+}
+
+pub struct Stats;
+
+impl Stats {
+    pub fn get() -> ApolloInstanceStats {
+        STATE.with(|state| state.borrow().stats.clone())
+    }
+
+    pub fn add_deposit(amount: &Nat) {
+        STATE.with(|state| state.borrow_mut().stats.total_deposits += amount.clone());
+    }
+
+    pub fn add_fee(fee: &Nat) {
+        STATE.with(|state| state.borrow_mut().stats.total_fees += fee.clone());
+    }
+
+    pub fn add_gas_spent(amount: &Nat) {
+        STATE.with(|state| state.borrow_mut().stats.total_gas_spent += amount.clone());
+    }
+
+    /// Records the tick, only the last `MAX_TICK_CYCLES` ticks are kept
+    pub fn add_tick(started_at: u64, cycles_spent: u128) {
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+
+            state.stats.total_cycles_spent += Nat::from(cycles_spent);
+            state.tick_cycles.push(TickCycles {
+                started_at,
+                cycles_spent: Nat::from(cycles_spent),
+            });
+
+            let len = state.tick_cycles.len();
+            if len > MAX_TICK_CYCLES {
+                state.tick_cycles.drain(..len - MAX_TICK_CYCLES);
+            }
+        });
+    }
+
+    pub fn get_ticks() -> Vec<TickCycles> {
+        STATE.with(|state| state.borrow().tick_cycles.clone())
+    }
+}
@@ -0,0 +1,297 @@
+use std::borrow::{Borrow, BorrowMut};
+
+use apollo_utils::{address, errors::ApolloInstanceError, memory::Cbor, nat::ToNativeTypes};
+use candid::{CandidType, Nat};
+use ic_stable_structures::StableBTreeMap;
+use ic_web3_rs::types::U256;
+use serde::{Deserialize, Serialize};
+
+use crate::{log, memory::VMemory};
+
+use super::{ApolloCoordinatorRequest, STATE};
+
+/// Request ids of the subscription deliveries have the highest bit set,
+/// so they don't collide with the coordinator request ids
+pub const SUBSCRIPTION_REQUEST_ID_OFFSET: u64 = 1 << 63;
+const BPS: u128 = 10_000;
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct SubscriptionRequest {
+    /// Contract, which receives the updates, it must be granted to use the sponsor's balance
+    pub contract: String,
+    pub feed_id: String,
+    /// Heartbeat, if the deviation threshold is set
+    pub interval_sec: u64,
+    pub deviation_bps: Option<u64>,
+    pub callback_gas_limit: Nat,
+}
+
+/// Feed, which is pushed to the contract every `interval_sec` without on-chain requests.
+/// With a deviation threshold, the feed is also pushed once its value moves more than
+/// `deviation_bps` from the last delivered one.
+/// Deliveries are charged from the balance of the user, who granted the contract
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct Subscription {
+    pub id: u64,
+    pub contract: String,
+    pub feed_id: String,
+    pub interval_sec: u64,
+    #[serde(default)]
+    pub deviation_bps: Option<u64>,
+    pub callback_gas_limit: Nat,
+    pub last_delivered_at: Option<u64>,
+    #[serde(default)]
+    pub last_delivered_value: Option<u64>,
+}
+
+impl Subscription {
+    /// The interval or the heartbeat has elapsed since the last delivery
+    pub fn is_due(&self, now: u64) -> bool {
+        self.last_delivered_at
+            .map_or(true, |delivered_at| now >= delivered_at + self.interval_sec)
+    }
+
+    /// Subscriptions with a deviation threshold are checked on every tick
+    pub fn should_check(&self, now: u64) -> bool {
+        self.is_due(now) || self.deviation_bps.is_some()
+    }
+
+    /// The value moved from the last delivered one more than the deviation threshold
+    pub fn is_deviated(&self, value: Option<u64>) -> bool {
+        let (Some(deviation_bps), Some(value)) = (self.deviation_bps, value) else {
+            return false;
+        };
+
+        let Some(last_value) = self.last_delivered_value else {
+            return true;
+        };
+
+        if last_value == 0 {
+            return value != 0;
+        }
+
+        let deviation = (value.abs_diff(last_value) as u128) * BPS / last_value as u128;
+
+        deviation > deviation_bps as u128
+    }
+
+    /// Request id, which is delivered to the contract alongside the data
+    pub fn request_id(&self) -> u64 {
+        SUBSCRIPTION_REQUEST_ID_OFFSET | self.id
+    }
+
+    pub fn id_from_request_id(request_id: u64) -> Option<u64> {
+        (request_id & SUBSCRIPTION_REQUEST_ID_OFFSET != 0)
+            .then_some(request_id & !SUBSCRIPTION_REQUEST_ID_OFFSET)
+    }
+
+    /// Delivery is processed the same way as a data feed request from the coordinator
+    pub fn to_request(&self) -> Result<ApolloCoordinatorRequest, ApolloInstanceError> {
+        Ok(ApolloCoordinatorRequest::DataFeed {
+            request_id: U256::from(self.request_id()),
+            feed_id: self.feed_id.clone(),
+            callback_gas_limit: self.callback_gas_limit.to_u256(),
+            requester: address::to_h160(&self.contract)?,
+        })
+    }
+}
+
+/// subscription id => subscription
+pub struct Subscriptions(StableBTreeMap<u64, Cbor<Subscription>, VMemory>);
+
+impl Default for Subscriptions {
+    fn default() -> Self {
+        Self(StableBTreeMap::init(
+            crate::memory::get_subscriptions_memory(),
+        ))
+    }
+}
+
+impl Subscriptions {
+    pub fn add(request: SubscriptionRequest) -> Result<u64, ApolloInstanceError> {
+        let contract = address::normalize(&request.contract)?;
+
+        if request.interval_sec == 0 {
+            return Err(ApolloInstanceError::InvalidSubscription(
+                "interval must be greater than 0".to_string(),
+            ));
+        }
+
+        if request.deviation_bps == Some(0) {
+            return Err(ApolloInstanceError::InvalidSubscription(
+                "deviation threshold must be greater than 0".to_string(),
+            ));
+        }
+
+        let subscription = STATE.with(|state| {
+            let mut state = state.borrow_mut();
+
+            // the subscriptions, which were added before the counter, are taken into account
+            let id = state
+                .subscriptions
+                .0
+                .last_key_value()
+                .map_or(0, |(id, _)| id + 1)
+                .max(state.next_subscription_id);
+            state.next_subscription_id = id + 1;
+
+            let inner = state.subscriptions.0.borrow_mut();
+            let subscription = Subscription {
+                id,
+                contract,
+                feed_id: request.feed_id,
+                interval_sec: request.interval_sec,
+                deviation_bps: request.deviation_bps,
+                callback_gas_limit: request.callback_gas_limit,
+                last_delivered_at: None,
+                last_delivered_value: None,
+            };
+
+            inner.insert(id, Cbor(subscription.clone()));
+
+            subscription
+        });
+
+        log!("[SUBSCRIPTIONS] added {:?}", subscription);
+
+        Ok(subscription.id)
+    }
+
+    pub fn remove(id: u64) -> Result<(), ApolloInstanceError> {
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            let inner = state.subscriptions.0.borrow_mut();
+
+            inner
+                .remove(&id)
+                .ok_or(ApolloInstanceError::SubscriptionNotFound(id))
+        })?;
+
+        log!("[SUBSCRIPTIONS] removed {}", id);
+
+        Ok(())
+    }
+
+    pub fn get(id: u64) -> Result<Subscription, ApolloInstanceError> {
+        STATE.with(|state| {
+            let state = state.borrow();
+            let inner = state.subscriptions.0.borrow();
+
+            inner
+                .get(&id)
+                .map(|subscription| subscription.0)
+                .ok_or(ApolloInstanceError::SubscriptionNotFound(id))
+        })
+    }
+
+    pub fn get_all() -> Vec<Subscription> {
+        STATE.with(|state| {
+            let state = state.borrow();
+            let inner = state.subscriptions.0.borrow();
+
+            inner
+                .iter()
+                .map(|(_, subscription)| subscription.0)
+                .collect()
+        })
+    }
+
+    /// Subscriptions, which should be checked for a delivery on this tick
+    pub fn get_to_check(now: u64) -> Vec<Subscription> {
+        Self::get_all()
+            .into_iter()
+            .filter(|subscription| subscription.should_check(now))
+            .collect()
+    }
+
+    /// Records the delivered values by subscription ids
+    pub fn mark_delivered(deliveries: &[(u64, Option<u64>)], delivered_at: u64) {
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            let inner = state.subscriptions.0.borrow_mut();
+
+            for (id, value) in deliveries {
+                // the subscription could be removed while the delivery was processed
+                if let Some(Cbor(mut subscription)) = inner.get(id) {
+                    subscription.last_delivered_at = Some(delivered_at);
+                    subscription.last_delivered_value = *value;
+                    inner.insert(*id, Cbor(subscription));
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscription_request_id() {
+        let subscription = Subscription {
+            id: 5,
+            contract: "0x0000000000000000000000000000000000000001".to_string(),
+            feed_id: "ETH/USD".to_string(),
+            interval_sec: 60,
+            deviation_bps: None,
+            callback_gas_limit: Nat::from(100_000),
+            last_delivered_at: None,
+            last_delivered_value: None,
+        };
+
+        assert_eq!(
+            Subscription::id_from_request_id(subscription.request_id()),
+            Some(5)
+        );
+        assert_eq!(Subscription::id_from_request_id(5), None);
+    }
+
+    #[test]
+    fn test_subscription_is_due() {
+        let mut subscription = Subscription {
+            id: 0,
+            contract: "0x0000000000000000000000000000000000000001".to_string(),
+            feed_id: "ETH/USD".to_string(),
+            interval_sec: 60,
+            deviation_bps: None,
+            callback_gas_limit: Nat::from(100_000),
+            last_delivered_at: None,
+            last_delivered_value: None,
+        };
+        assert!(subscription.is_due(0));
+
+        subscription.last_delivered_at = Some(100);
+        assert!(!subscription.is_due(159));
+        assert!(subscription.is_due(160));
+    }
+
+    #[test]
+    fn test_subscription_is_deviated() {
+        let mut subscription = Subscription {
+            id: 0,
+            contract: "0x0000000000000000000000000000000000000001".to_string(),
+            feed_id: "ETH/USD".to_string(),
+            interval_sec: 3600,
+            deviation_bps: None,
+            callback_gas_limit: Nat::from(100_000),
+            last_delivered_at: Some(100),
+            last_delivered_value: Some(10_000),
+        };
+        assert!(!subscription.is_deviated(Some(20_000)));
+        assert!(!subscription.should_check(200));
+
+        subscription.deviation_bps = Some(50);
+        assert!(subscription.should_check(200));
+        assert!(!subscription.is_deviated(Some(10_050)));
+        assert!(subscription.is_deviated(Some(10_051)));
+        assert!(subscription.is_deviated(Some(9_949)));
+        assert!(!subscription.is_deviated(None));
+
+        subscription.last_delivered_value = None;
+        assert!(subscription.is_deviated(Some(10_000)));
+
+        subscription.last_delivered_value = Some(0);
+        assert!(!subscription.is_deviated(Some(0)));
+        assert!(subscription.is_deviated(Some(1)));
+    }
+}
@@ -0,0 +1,74 @@
+use std::borrow::{Borrow, BorrowMut};
+
+use apollo_utils::memory::Cbor;
+use candid::CandidType;
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{log, memory::VMemory};
+
+use super::STATE;
+
+/// Coordinator event, which was skipped by the logs polling
+#[derive(Clone, Debug, Default, CandidType, Serialize, Deserialize)]
+pub struct UnknownEvent {
+    pub topic: String,
+    /// Why the last event with this topic was skipped
+    pub reason: String,
+    pub count: u64,
+    pub last_block: u64,
+    pub last_tx_hash: String,
+}
+
+/// topic => the last skipped event with this topic
+pub struct UnknownEvents(StableBTreeMap<String, Cbor<UnknownEvent>, VMemory>);
+
+impl Default for UnknownEvents {
+    fn default() -> Self {
+        Self(StableBTreeMap::init(
+            crate::memory::get_unknown_events_memory(),
+        ))
+    }
+}
+
+impl UnknownEvents {
+    pub fn add(topic: String, reason: String, block: u64, tx_hash: String) {
+        log!(
+            "[UNKNOWN EVENTS] topic = {}, reason = {}, block = {}, tx hash = {}",
+            topic,
+            reason,
+            block,
+            tx_hash
+        );
+
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            let inner = state.unknown_events.0.borrow_mut();
+
+            let count = inner
+                .get(&topic)
+                .map(|event| event.0.count)
+                .unwrap_or_default();
+
+            inner.insert(
+                topic.clone(),
+                Cbor(UnknownEvent {
+                    topic,
+                    reason,
+                    count: count + 1,
+                    last_block: block,
+                    last_tx_hash: tx_hash,
+                }),
+            );
+        });
+    }
+
+    pub fn get_all() -> Vec<UnknownEvent> {
+        STATE.with(|state| {
+            let state = state.borrow();
+            let inner = state.unknown_events.0.borrow();
+
+            inner.iter().map(|(_, event)| event.0).collect()
+        })
+    }
+}
@@ -1,6 +1,15 @@
+use std::collections::BTreeMap;
+
 use candid::{CandidType, Nat};
 use serde::{Deserialize, Serialize};
 
+use crate::data_source::DataSourceAllowlist;
+
+const BPS: u64 = 10_000;
+// wei in a cent of the native token unit, the native token has 18 decimals
+const WEI_PER_CENT_EXPONENT: u64 = 16;
+const TRILLION_CYCLES: u64 = 1_000_000_000_000;
+
 #[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
 pub struct ApolloInstanceInit {
     pub apollos_fee: Nat,
@@ -16,12 +25,144 @@ pub struct ApolloInstanceInit {
     pub min_balance: Nat,
 }
 
+/// Version of the coordinator contract ABI, which is used to decode its events
+#[derive(Clone, Copy, Debug, Default, PartialEq, CandidType, Serialize, Deserialize)]
+pub enum CoordinatorAbiVersion {
+    #[default]
+    V1,
+}
+
+/// Data feed request from another chain, which is delivered by the target chain instance.
+/// The requester is charged on the source chain
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct RelayedRequest {
+    pub source_chain_id: Nat,
+    pub target_chain_id: Nat,
+    pub request_id: Nat,
+    pub feed_id: String,
+    pub callback_gas_limit: Nat,
+    /// Contract on the target chain, which receives the data
+    pub target: String,
+}
+
+/// Totals of the instance, amounts are in wei
+#[derive(Serialize, Debug, Deserialize, CandidType, Clone, Default)]
+pub struct ApolloInstanceStats {
+    pub total_deposits: Nat,
+    /// Fees, which were charged from the requesters on top of the gas
+    pub total_fees: Nat,
+    /// Gas, which was paid by the AMA for the callbacks
+    pub total_gas_spent: Nat,
+    /// Cycles, which were spent by the instance ticks
+    #[serde(default)]
+    pub total_cycles_spent: Nat,
+}
+
+/// Result of the check, whether the AMA can cover the user balances
+#[derive(Serialize, Debug, Deserialize, CandidType, Clone, PartialEq)]
+pub struct SolvencyStatus {
+    pub ama_balance: Nat,
+    pub total_user_balances: Nat,
+    pub min_ama_balance: Nat,
+    pub is_solvent: bool,
+    pub checked_at: u64,
+}
+
+impl SolvencyStatus {
+    /// The AMA is solvent, if it covers every user balance and holds at least the configured minimum
+    pub fn new(
+        ama_balance: Nat,
+        total_user_balances: Nat,
+        min_ama_balance: Nat,
+        checked_at: u64,
+    ) -> Self {
+        let is_solvent = ama_balance >= total_user_balances && ama_balance >= min_ama_balance;
+
+        Self {
+            ama_balance,
+            total_user_balances,
+            min_ama_balance,
+            is_solvent,
+            checked_at,
+        }
+    }
+}
+
+/// Types of requests, for which the Sybil signature is appended to the delivered data
+#[derive(Serialize, Debug, Deserialize, CandidType, Clone, Default)]
+pub struct SybilSignatureConfig {
+    pub default_price_feed: bool,
+    pub custom_price_feed: bool,
+    pub custom_number: bool,
+    pub custom_string: bool,
+}
+
+/// Kind of the request, which is priced by the fee schedule
+#[derive(Clone, Copy, Debug, PartialEq, CandidType, Serialize, Deserialize)]
+pub enum RequestKind {
+    DataFeed,
+    Random,
+}
+
+/// Discount for the requesters, which have got at least `min_requests` callbacks
+#[derive(Serialize, Debug, Deserialize, CandidType, Clone, PartialEq)]
+pub struct VolumeDiscount {
+    pub min_requests: u64,
+    pub discount_bps: u64,
+}
+
+/// Fees, which are charged on top of the used gas, `apollos_fee` is charged if the fee is not set
+#[derive(Serialize, Debug, Deserialize, CandidType, Clone, Default)]
+pub struct FeeSchedule {
+    pub data_feed_fee: Option<Nat>,
+    pub random_fee: Option<Nat>,
+    // feed_id => fee, overrides the data feed fee
+    pub feed_fees: BTreeMap<String, Nat>,
+    // the discount with the highest reached `min_requests` is applied
+    pub volume_discounts: Vec<VolumeDiscount>,
+}
+
+/// Denomination of `apollos_fee` and the fee schedule
+#[derive(Serialize, Debug, Deserialize, CandidType, Clone, Default, PartialEq)]
+pub enum FeeDenomination {
+    /// Wei of the chain's native token
+    #[default]
+    Native,
+    /// USD cents, converted to wei at charge time by the Sybil price feed of the native token, e.g. "ETH/USD"
+    UsdCents { price_feed_id: String },
+}
+
+/// Price of the native token in USD, which converted the fee of the callback
+#[derive(Serialize, Debug, Deserialize, CandidType, Clone, PartialEq)]
+pub struct FeeConversionRate {
+    pub price_feed_id: String,
+    pub rate: u64,
+    pub decimals: u64,
+}
+
+impl FeeConversionRate {
+    /// Converts USD cents to wei: `cents * 10^16 * 10^decimals / rate`
+    pub fn to_native(&self, cents: Nat) -> Nat {
+        if self.rate == 0 {
+            return Nat::from(0);
+        }
+
+        cents * pow10(WEI_PER_CENT_EXPONENT + self.decimals) / Nat::from(self.rate)
+    }
+}
+
+fn pow10(exponent: u64) -> Nat {
+    (0..exponent).fold(Nat::from(1u64), |acc, _| acc * Nat::from(10u64))
+}
+
 #[derive(Serialize, Debug, Deserialize, CandidType, Clone)]
 pub struct ApolloInstanceMetadata {
     pub apollos_fee: Nat,
     pub key_name: String,
     pub chain_id: Nat,
     pub chain_rpc: String,
+    /// Coordinator, the instance was created with. It is not updated, when the coordinators are added or removed,
+    /// so it may not be watched anymore, `get_coordinators` returns the watched ones
     pub apollo_coordinator: String,
     pub apollo_evm_address: Option<String>,
     pub multicall_address: String,
@@ -29,6 +170,30 @@ pub struct ApolloInstanceMetadata {
     pub evm_rpc_canister: String,       // Principal is not supported by ciborium
     pub block_gas_limit: Nat,
     pub min_balance: Nat,
+    // random words are delivered with the AMA signature over (request_id, seed)
+    #[serde(default)]
+    pub sign_randomness: bool,
+    #[serde(default)]
+    pub sybil_signature: SybilSignatureConfig,
+    // price feeds older than this are not delivered, `None` disables the check
+    #[serde(default)]
+    pub max_staleness_sec: Option<u64>,
+    // feed_id => max staleness, overrides the global one
+    #[serde(default)]
+    pub feeds_max_staleness_sec: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub fee_schedule: FeeSchedule,
+    #[serde(default)]
+    pub fee_denomination: FeeDenomination,
+    // the fulfillment is paused, when the AMA balance is below it
+    #[serde(default)]
+    pub min_ama_balance: Nat,
+    // wei, which the requester is charged per trillion cycles attached to the calls of the request, 0 disables the surcharge
+    #[serde(default)]
+    pub cycles_surcharge_rate: Nat,
+    // `https://` and `canister:` feeds are rejected, unless their source is in the allowlist
+    #[serde(default)]
+    pub data_source_allowlist: DataSourceAllowlist,
 }
 
 #[derive(Serialize, Deserialize, CandidType, Clone)]
@@ -36,12 +201,20 @@ pub struct UpdateMetadata {
     pub apollos_fee: Option<Nat>,
     pub chain_id: Option<Nat>,
     pub chain_rpc: Option<String>,
-    pub apollo_coordinator: Option<String>,
     pub multicall_address: Option<String>,
     pub sybil_canister_address: Option<String>, // Principal is not supported by ciborium
     pub evm_rpc_canister: Option<String>,       // Principal is not supported by ciborium
     pub block_gas_limit: Option<Nat>,
     pub min_balance: Option<Nat>,
+    pub sign_randomness: Option<bool>,
+    pub sybil_signature: Option<SybilSignatureConfig>,
+    pub max_staleness_sec: Option<u64>,
+    pub feeds_max_staleness_sec: Option<BTreeMap<String, u64>>,
+    pub fee_schedule: Option<FeeSchedule>,
+    pub fee_denomination: Option<FeeDenomination>,
+    pub min_ama_balance: Option<Nat>,
+    pub cycles_surcharge_rate: Option<Nat>,
+    pub data_source_allowlist: Option<DataSourceAllowlist>,
 }
 
 impl ApolloInstanceMetadata {
@@ -55,9 +228,6 @@ impl ApolloInstanceMetadata {
         if let Some(chain_rpc) = update.chain_rpc {
             self.chain_rpc = chain_rpc;
         }
-        if let Some(apollo_coordinator) = update.apollo_coordinator {
-            self.apollo_coordinator = apollo_coordinator;
-        }
         if let Some(multicall_address) = update.multicall_address {
             self.multicall_address = multicall_address;
         }
@@ -73,6 +243,92 @@ impl ApolloInstanceMetadata {
         if let Some(min_balance) = update.min_balance {
             self.min_balance = min_balance;
         }
+        if let Some(sign_randomness) = update.sign_randomness {
+            self.sign_randomness = sign_randomness;
+        }
+        if let Some(sybil_signature) = update.sybil_signature {
+            self.sybil_signature = sybil_signature;
+        }
+        // 0 disables the global staleness check
+        if let Some(max_staleness_sec) = update.max_staleness_sec {
+            self.max_staleness_sec = (max_staleness_sec != 0).then_some(max_staleness_sec);
+        }
+        if let Some(feeds_max_staleness_sec) = update.feeds_max_staleness_sec {
+            self.feeds_max_staleness_sec = feeds_max_staleness_sec;
+        }
+        if let Some(fee_schedule) = update.fee_schedule {
+            self.fee_schedule = fee_schedule;
+        }
+        if let Some(fee_denomination) = update.fee_denomination {
+            self.fee_denomination = fee_denomination;
+        }
+        if let Some(min_ama_balance) = update.min_ama_balance {
+            self.min_ama_balance = min_ama_balance;
+        }
+        if let Some(cycles_surcharge_rate) = update.cycles_surcharge_rate {
+            self.cycles_surcharge_rate = cycles_surcharge_rate;
+        }
+        if let Some(data_source_allowlist) = update.data_source_allowlist {
+            self.data_source_allowlist = data_source_allowlist;
+        }
+    }
+
+    /// Max staleness of the feed, the per-feed setting takes precedence over the global one
+    pub fn max_staleness_sec(&self, feed_id: &str) -> Option<u64> {
+        self.feeds_max_staleness_sec
+            .get(feed_id)
+            .copied()
+            .or(self.max_staleness_sec)
+    }
+
+    /// Fee for the request in the fee denomination, a multi feed request is charged the highest fee of its feeds
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - Kind of the request
+    /// * `feed_ids` - Requested feeds, empty for the random requests
+    /// * `requests` - Number of the callbacks, which the requester has already got
+    pub fn fee(&self, kind: RequestKind, feed_ids: &[String], requests: u64) -> Nat {
+        let schedule = &self.fee_schedule;
+
+        let fee = match kind {
+            RequestKind::Random => schedule
+                .random_fee
+                .clone()
+                .unwrap_or_else(|| self.apollos_fee.clone()),
+            RequestKind::DataFeed => {
+                let data_feed_fee = schedule
+                    .data_feed_fee
+                    .clone()
+                    .unwrap_or_else(|| self.apollos_fee.clone());
+
+                feed_ids
+                    .iter()
+                    .map(|feed_id| {
+                        schedule
+                            .feed_fees
+                            .get(feed_id)
+                            .cloned()
+                            .unwrap_or_else(|| data_feed_fee.clone())
+                    })
+                    .max()
+                    .unwrap_or(data_feed_fee)
+            }
+        };
+
+        let discount_bps = schedule
+            .volume_discounts
+            .iter()
+            .filter(|discount| requests >= discount.min_requests)
+            .max_by_key(|discount| discount.min_requests)
+            .map_or(0, |discount| discount.discount_bps.min(BPS));
+
+        fee * Nat::from(BPS - discount_bps) / Nat::from(BPS)
+    }
+
+    /// Surcharge in wei for the cycles, which were attached to the calls of the request
+    pub fn cycles_surcharge(&self, cycles: &Nat) -> Nat {
+        cycles.clone() * self.cycles_surcharge_rate.clone() / Nat::from(TRILLION_CYCLES)
     }
 }
 
@@ -90,6 +346,15 @@ impl Default for ApolloInstanceMetadata {
             evm_rpc_canister: "".to_string(),
             block_gas_limit: Nat::from(0),
             min_balance: Nat::from(0),
+            sign_randomness: false,
+            sybil_signature: SybilSignatureConfig::default(),
+            max_staleness_sec: None,
+            feeds_max_staleness_sec: BTreeMap::new(),
+            fee_schedule: FeeSchedule::default(),
+            fee_denomination: FeeDenomination::default(),
+            min_ama_balance: Nat::from(0),
+            cycles_surcharge_rate: Nat::from(0),
+            data_source_allowlist: DataSourceAllowlist::default(),
         }
     }
 }
@@ -109,6 +374,116 @@ impl From<ApolloInstanceInit> for ApolloInstanceMetadata {
             sybil_canister_address: init.sybil_canister_address,
             evm_rpc_canister: init.evm_rpc_canister,
             min_balance: init.min_balance,
+            sign_randomness: false,
+            sybil_signature: SybilSignatureConfig::default(),
+            max_staleness_sec: None,
+            feeds_max_staleness_sec: BTreeMap::new(),
+            fee_schedule: FeeSchedule::default(),
+            fee_denomination: FeeDenomination::default(),
+            min_ama_balance: Nat::from(0),
+            cycles_surcharge_rate: Nat::from(0),
+            data_source_allowlist: DataSourceAllowlist::default(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> ApolloInstanceMetadata {
+        ApolloInstanceMetadata {
+            apollos_fee: Nat::from(100u64),
+            fee_schedule: FeeSchedule {
+                data_feed_fee: None,
+                random_fee: Some(Nat::from(300u64)),
+                feed_fees: BTreeMap::from([("PREMIUM".to_string(), Nat::from(1_000u64))]),
+                volume_discounts: vec![
+                    VolumeDiscount {
+                        min_requests: 100,
+                        discount_bps: 5_000,
+                    },
+                    VolumeDiscount {
+                        min_requests: 10,
+                        discount_bps: 1_000,
+                    },
+                ],
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fee() {
+        let metadata = metadata();
+        let feeds = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            metadata.fee(RequestKind::DataFeed, &feeds(&["ETH/USD"]), 0),
+            Nat::from(100u64)
+        );
+        assert_eq!(metadata.fee(RequestKind::Random, &[], 0), Nat::from(300u64));
+        assert_eq!(
+            metadata.fee(RequestKind::DataFeed, &feeds(&["ETH/USD", "PREMIUM"]), 0),
+            Nat::from(1_000u64)
+        );
+
+        assert_eq!(
+            metadata.fee(RequestKind::DataFeed, &feeds(&["PREMIUM"]), 10),
+            Nat::from(900u64)
+        );
+        assert_eq!(
+            metadata.fee(RequestKind::DataFeed, &feeds(&["PREMIUM"]), 150),
+            Nat::from(500u64)
+        );
+    }
+
+    #[test]
+    fn test_fee_conversion_rate() {
+        // 1 ETH = 2000.00 USD
+        let rate = FeeConversionRate {
+            price_feed_id: "ETH/USD".to_string(),
+            rate: 200_000,
+            decimals: 2,
+        };
+
+        // 1 USD = 0.0005 ETH
+        assert_eq!(
+            rate.to_native(Nat::from(100u64)),
+            Nat::from(500_000_000_000_000u64)
+        );
+        assert_eq!(rate.to_native(Nat::from(0u64)), Nat::from(0u64));
+    }
+
+    #[test]
+    fn test_solvency_status() {
+        let status = |ama_balance: u64, total_user_balances: u64, min_ama_balance: u64| {
+            SolvencyStatus::new(
+                Nat::from(ama_balance),
+                Nat::from(total_user_balances),
+                Nat::from(min_ama_balance),
+                0,
+            )
+            .is_solvent
+        };
+
+        assert!(status(100, 100, 0));
+        assert!(status(100, 50, 100));
+        assert!(!status(99, 100, 0));
+        assert!(!status(100, 50, 101));
+    }
+
+    #[test]
+    fn test_cycles_surcharge() {
+        let mut metadata = metadata();
+        let cycles = Nat::from(3_000_000_000u64);
+
+        assert_eq!(metadata.cycles_surcharge(&cycles), Nat::from(0u64));
+
+        metadata.cycles_surcharge_rate = Nat::from(2_000_000_000_000u64);
+        assert_eq!(
+            metadata.cycles_surcharge(&cycles),
+            Nat::from(6_000_000_000u64)
+        );
+    }
+}
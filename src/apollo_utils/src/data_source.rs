@@ -0,0 +1,489 @@
+use candid::{CandidType, Nat, Principal};
+use ic_cdk::api::{
+    call::call_raw,
+    management_canister::http_request::{
+        http_request, CanisterHttpRequestArgument, HttpMethod, TransformContext,
+    },
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    errors::DataSourceError,
+    log, retry_until_success,
+    sybil::{get_sybil_feed, AssetDataResult, SybilAssetData},
+};
+
+pub const CANISTER_PREFIX: &str = "canister:";
+pub const HTTPS_PREFIX: &str = "https://";
+pub const SYBIL_PREFIX: &str = "sybil:";
+
+const MAX_RESPONSE_BYTES: u64 = 64 * 1024;
+// https://internetcomputer.org/docs/current/developer-docs/gas-cost, 13-node subnet
+const HTTP_OUTCALL_BASE_CYCLES: u128 = 49_140_000;
+const HTTP_OUTCALL_REQUEST_BYTE_CYCLES: u128 = 5_200;
+const HTTP_OUTCALL_RESPONSE_BYTE_CYCLES: u128 = 10_400;
+
+/// Non-Sybil sources, which may be requested, the feed ids come from the chain,
+/// so the instance doesn't pay for the outcalls and calls to arbitrary sources
+#[derive(Clone, Debug, Default, PartialEq, CandidType, Serialize, Deserialize)]
+pub struct DataSourceAllowlist {
+    /// Hosts of the `https://` sources, e.g. `api.example.com`
+    pub hosts: Vec<String>,
+    /// Principals of the `canister:` sources
+    pub canisters: Vec<String>,
+}
+
+impl DataSourceAllowlist {
+    fn is_canister_allowed(&self, canister_id: &Principal) -> bool {
+        self.canisters
+            .iter()
+            .any(|allowed| Principal::from_text(allowed).as_ref() == Ok(canister_id))
+    }
+
+    fn is_url_allowed(&self, url: &str) -> bool {
+        let Some(host) = url_host(url) else {
+            return false;
+        };
+
+        self.hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+    }
+}
+
+/// Source of the `DataFeed` request data, chosen by the prefix of the `dataFeedId`:
+/// - `canister:<principal>/<method>` - IC canister method without arguments, returning `text`, `nat` or `nat64`
+/// - `https://<url>#<json path>` - HTTPS JSON endpoint, the value is selected with the JSON path, e.g. `$.data[0].price`
+/// - `sybil:<feed id>` or no prefix - Sybil feed
+#[derive(Clone, Debug, PartialEq)]
+pub enum DataSource {
+    Sybil {
+        feed_id: String,
+    },
+    Canister {
+        canister_id: Principal,
+        method: String,
+    },
+    Https {
+        url: String,
+        json_path: String,
+    },
+}
+
+impl DataSource {
+    /// Parses the source of the feed, the canisters and the hosts, which are not in the allowlist, are rejected
+    pub fn from_feed_id(
+        feed_id: &str,
+        allowlist: &DataSourceAllowlist,
+    ) -> Result<Self, DataSourceError> {
+        if let Some(source) = feed_id.strip_prefix(CANISTER_PREFIX) {
+            let (canister_id, method) = source
+                .split_once('/')
+                .filter(|(_, method)| !method.is_empty())
+                .ok_or_else(|| DataSourceError::InvalidFeedId(feed_id.to_string()))?;
+
+            let canister_id = Principal::from_text(canister_id)
+                .map_err(|_| DataSourceError::InvalidFeedId(feed_id.to_string()))?;
+
+            if !allowlist.is_canister_allowed(&canister_id) {
+                return Err(DataSourceError::InvalidFeedId(feed_id.to_string()));
+            }
+
+            return Ok(Self::Canister {
+                canister_id,
+                method: method.to_string(),
+            });
+        }
+
+        if feed_id.starts_with(HTTPS_PREFIX) {
+            let (url, json_path) = feed_id
+                .rsplit_once('#')
+                .ok_or_else(|| DataSourceError::InvalidFeedId(feed_id.to_string()))?;
+
+            parse_json_path(json_path)?;
+
+            if !allowlist.is_url_allowed(url) {
+                return Err(DataSourceError::InvalidFeedId(feed_id.to_string()));
+            }
+
+            return Ok(Self::Https {
+                url: url.to_string(),
+                json_path: json_path.to_string(),
+            });
+        }
+
+        Ok(Self::Sybil {
+            feed_id: feed_id
+                .strip_prefix(SYBIL_PREFIX)
+                .unwrap_or(feed_id)
+                .to_string(),
+        })
+    }
+
+    /// Cycles, which are attached to the outcall of the source, the canister calls don't attach cycles
+    pub fn outcall_cycles(&self) -> u128 {
+        match self {
+            Self::Https { url, .. } => https_outcall_cycles(url),
+            Self::Sybil { .. } | Self::Canister { .. } => 0,
+        }
+    }
+
+    /// Fetches the data, the non-Sybil data is returned as a custom number or string,
+    /// identified by the whole `feed_id` and without a signature
+    pub async fn fetch(
+        &self,
+        feed_id: &str,
+        sybil_canister_address: String,
+    ) -> Result<AssetDataResult, DataSourceError> {
+        let data = match self {
+            Self::Sybil {
+                feed_id: sybil_feed_id,
+            } => return Ok(get_sybil_feed(sybil_canister_address, sybil_feed_id.clone()).await?),
+            Self::Canister {
+                canister_id,
+                method,
+            } => fetch_from_canister(feed_id, *canister_id, method).await?,
+            Self::Https { url, json_path } => fetch_from_https(feed_id, url, json_path).await?,
+        };
+
+        Ok(AssetDataResult {
+            data,
+            signature: None,
+        })
+    }
+}
+
+async fn fetch_from_canister(
+    feed_id: &str,
+    canister_id: Principal,
+    method: &str,
+) -> Result<SybilAssetData, DataSourceError> {
+    log!("[DATA SOURCE] Canister {canister_id} method {method} requested");
+
+    let args = candid::encode_args(()).expect("should be able to encode empty args");
+    let response = retry_until_success!(call_raw(canister_id, method, args.clone(), 0))
+        .map_err(|(code, msg)| DataSourceError::CanisterError(format!("{:?}: {}", code, msg)))?;
+
+    decode_candid_value(feed_id, &response)
+}
+
+fn decode_candid_value(feed_id: &str, response: &[u8]) -> Result<SybilAssetData, DataSourceError> {
+    if let Ok(value) = candid::decode_one::<String>(response) {
+        return Ok(SybilAssetData::CustomString {
+            id: feed_id.to_string(),
+            value,
+        });
+    }
+
+    let value = match candid::decode_one::<u64>(response) {
+        Ok(value) => value,
+        Err(_) => {
+            let value = candid::decode_one::<Nat>(response).map_err(|_| {
+                DataSourceError::InvalidResponse("expected text, nat or nat64".to_string())
+            })?;
+
+            u64::try_from(&value.0).map_err(|_| {
+                DataSourceError::InvalidResponse(format!("{value} doesn't fit into nat64"))
+            })?
+        }
+    };
+
+    Ok(SybilAssetData::CustomNumber {
+        id: feed_id.to_string(),
+        value,
+        decimals: 0,
+    })
+}
+
+/// Host of the `https://` url, `None` if the url has the user info
+fn url_host(url: &str) -> Option<&str> {
+    let authority = url
+        .strip_prefix(HTTPS_PREFIX)?
+        .split(['/', '?', '#'])
+        .next()?;
+
+    if authority.contains('@') {
+        return None;
+    }
+
+    authority.split(':').next().filter(|host| !host.is_empty())
+}
+
+fn https_outcall_cycles(url: &str) -> u128 {
+    HTTP_OUTCALL_BASE_CYCLES
+        + HTTP_OUTCALL_REQUEST_BYTE_CYCLES * url.len() as u128
+        + HTTP_OUTCALL_RESPONSE_BYTE_CYCLES * MAX_RESPONSE_BYTES as u128
+}
+
+async fn fetch_from_https(
+    feed_id: &str,
+    url: &str,
+    json_path: &str,
+) -> Result<SybilAssetData, DataSourceError> {
+    log!("[DATA SOURCE] Https endpoint {url} requested");
+
+    let request = CanisterHttpRequestArgument {
+        url: url.to_string(),
+        max_response_bytes: Some(MAX_RESPONSE_BYTES),
+        method: HttpMethod::GET,
+        headers: vec![],
+        body: None,
+        transform: Some(TransformContext::from_name("transform".to_string(), vec![])),
+    };
+
+    let (response,) =
+        retry_until_success!(http_request(request.clone(), https_outcall_cycles(url)))
+            .map_err(|(code, msg)| DataSourceError::HttpError(format!("{:?}: {}", code, msg)))?;
+
+    if response.status != Nat::from(200u32) {
+        return Err(DataSourceError::HttpError(format!(
+            "status: {}",
+            response.status
+        )));
+    }
+
+    let json: Value = serde_json::from_slice(&response.body)
+        .map_err(|err| DataSourceError::InvalidResponse(err.to_string()))?;
+
+    json_value_to_asset_data(feed_id, select_json_value(&json, json_path)?)
+}
+
+fn json_value_to_asset_data(
+    feed_id: &str,
+    value: &Value,
+) -> Result<SybilAssetData, DataSourceError> {
+    match value {
+        Value::String(value) => Ok(SybilAssetData::CustomString {
+            id: feed_id.to_string(),
+            value: value.clone(),
+        }),
+        Value::Number(number) => {
+            let (value, decimals) = parse_decimal(&number.to_string())?;
+
+            Ok(SybilAssetData::CustomNumber {
+                id: feed_id.to_string(),
+                value,
+                decimals,
+            })
+        }
+        _ => Err(DataSourceError::InvalidResponse(format!(
+            "expected a string or a number, got: {value}"
+        ))),
+    }
+}
+
+/// Parses a non-negative decimal number without losing precision, e.g. `"12.345"` => `(12345, 3)`
+fn parse_decimal(number: &str) -> Result<(u64, u64), DataSourceError> {
+    let invalid_number =
+        || DataSourceError::InvalidResponse(format!("unsupported number: {number}"));
+
+    let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if integer.is_empty() || integer.starts_with('+') {
+        return Err(invalid_number());
+    }
+
+    let value = format!("{integer}{fraction}")
+        .parse::<u64>()
+        .map_err(|_| invalid_number())?;
+
+    Ok((value, fraction.len() as u64))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum JsonPathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Parses the subset of JSON path: `$`, `.key`, `[index]` and `['key']`
+fn parse_json_path(json_path: &str) -> Result<Vec<JsonPathSegment>, DataSourceError> {
+    let invalid_path = || DataSourceError::InvalidFeedId(format!("invalid json path: {json_path}"));
+
+    let mut path = json_path.strip_prefix('$').ok_or_else(invalid_path)?;
+    let mut segments = vec![];
+
+    while !path.is_empty() {
+        if let Some(rest) = path.strip_prefix('.') {
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(invalid_path());
+            }
+
+            segments.push(JsonPathSegment::Key(rest[..end].to_string()));
+            path = &rest[end..];
+        } else if let Some(rest) = path.strip_prefix('[') {
+            let (segment, rest) = rest.split_once(']').ok_or_else(invalid_path)?;
+
+            let key = segment
+                .strip_prefix('\'')
+                .and_then(|key| key.strip_suffix('\''))
+                .or_else(|| {
+                    segment
+                        .strip_prefix('"')
+                        .and_then(|key| key.strip_suffix('"'))
+                });
+
+            segments.push(match key {
+                Some(key) => JsonPathSegment::Key(key.to_string()),
+                None => JsonPathSegment::Index(segment.parse().map_err(|_| invalid_path())?),
+            });
+            path = rest;
+        } else {
+            return Err(invalid_path());
+        }
+    }
+
+    Ok(segments)
+}
+
+fn select_json_value<'a>(json: &'a Value, json_path: &str) -> Result<&'a Value, DataSourceError> {
+    parse_json_path(json_path)?
+        .into_iter()
+        .try_fold(json, |value, segment| {
+            match segment {
+                JsonPathSegment::Key(key) => value.get(&key),
+                JsonPathSegment::Index(index) => value.get(index),
+            }
+            .ok_or_else(|| {
+                DataSourceError::InvalidResponse(format!(
+                    "{json_path} is not found in the response"
+                ))
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowlist() -> DataSourceAllowlist {
+        DataSourceAllowlist {
+            hosts: vec!["example.com".to_string()],
+            canisters: vec!["ryjl3-tyaaa-aaaaa-aaaba-cai".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_from_feed_id() {
+        let from_feed_id = |feed_id| DataSource::from_feed_id(feed_id, &allowlist());
+
+        assert_eq!(
+            from_feed_id("ETH/USD").unwrap(),
+            DataSource::Sybil {
+                feed_id: "ETH/USD".to_string()
+            }
+        );
+        assert_eq!(
+            from_feed_id("sybil:ETH/USD").unwrap(),
+            DataSource::Sybil {
+                feed_id: "ETH/USD".to_string()
+            }
+        );
+        assert_eq!(
+            from_feed_id("canister:ryjl3-tyaaa-aaaaa-aaaba-cai/get_price").unwrap(),
+            DataSource::Canister {
+                canister_id: Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap(),
+                method: "get_price".to_string(),
+            }
+        );
+        assert_eq!(
+            from_feed_id("https://example.com/price?id=1#$.data[0].price").unwrap(),
+            DataSource::Https {
+                url: "https://example.com/price?id=1".to_string(),
+                json_path: "$.data[0].price".to_string(),
+            }
+        );
+
+        assert!(from_feed_id("canister:ryjl3-tyaaa-aaaaa-aaaba-cai").is_err());
+        assert!(from_feed_id("canister:invalid/get_price").is_err());
+        assert!(from_feed_id("https://example.com/price").is_err());
+        assert!(from_feed_id("https://example.com/price#data").is_err());
+
+        // not in the allowlist
+        assert!(from_feed_id("canister:rrkah-fqaaa-aaaaa-aaaaq-cai/get_price").is_err());
+        assert!(from_feed_id("https://evil.com/price#$.price").is_err());
+        assert!(from_feed_id("https://example.com@evil.com/price#$.price").is_err());
+        assert!(from_feed_id("https://EXAMPLE.com:443/price#$.price").is_ok());
+    }
+
+    #[test]
+    fn test_outcall_cycles() {
+        let from_feed_id = |feed_id| DataSource::from_feed_id(feed_id, &allowlist()).unwrap();
+
+        assert_eq!(from_feed_id("ETH/USD").outcall_cycles(), 0);
+        assert_eq!(
+            from_feed_id("canister:ryjl3-tyaaa-aaaaa-aaaba-cai/get_price").outcall_cycles(),
+            0
+        );
+        assert_eq!(
+            from_feed_id("https://example.com/price#$.price").outcall_cycles(),
+            HTTP_OUTCALL_BASE_CYCLES
+                + HTTP_OUTCALL_REQUEST_BYTE_CYCLES * 25
+                + HTTP_OUTCALL_RESPONSE_BYTE_CYCLES * MAX_RESPONSE_BYTES as u128
+        );
+    }
+
+    #[test]
+    fn test_select_json_value() {
+        let json: Value = serde_json::from_str(
+            r#"{"data": [{"price": 12.345, "symbol": "ETH"}], "weird key": {"value": 7}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            *select_json_value(&json, "$.data[0].price").unwrap(),
+            12.345
+        );
+        assert_eq!(
+            *select_json_value(&json, "$['data'][0]['symbol']").unwrap(),
+            "ETH"
+        );
+        assert_eq!(
+            *select_json_value(&json, "$[\"weird key\"].value").unwrap(),
+            7
+        );
+        assert!(select_json_value(&json, "$.data[1].price").is_err());
+        assert!(select_json_value(&json, "$.data..price").is_err());
+    }
+
+    #[test]
+    fn test_json_value_to_asset_data() {
+        let json: Value =
+            serde_json::from_str(r#"{"a": 12.345, "b": 7, "c": "text", "d": -1}"#).unwrap();
+
+        assert!(matches!(
+            json_value_to_asset_data("id", &json["a"]).unwrap(),
+            SybilAssetData::CustomNumber {
+                value: 12345,
+                decimals: 3,
+                ..
+            }
+        ));
+        assert!(matches!(
+            json_value_to_asset_data("id", &json["b"]).unwrap(),
+            SybilAssetData::CustomNumber {
+                value: 7,
+                decimals: 0,
+                ..
+            }
+        ));
+        assert!(matches!(
+            json_value_to_asset_data("id", &json["c"]).unwrap(),
+            SybilAssetData::CustomString { value, .. } if value == "text"
+        ));
+        assert!(json_value_to_asset_data("id", &json["d"]).is_err());
+        assert!(json_value_to_asset_data("id", &json).is_err());
+    }
+
+    #[test]
+    fn test_decode_candid_value() {
+        let encode = |value: Nat| candid::encode_one(value).unwrap();
+
+        assert!(matches!(
+            decode_candid_value("id", &encode(Nat::from(u64::MAX))).unwrap(),
+            SybilAssetData::CustomNumber { value, .. } if value == u64::MAX
+        ));
+        assert!(decode_candid_value("id", &encode(Nat::from(u64::MAX) + Nat::from(1u64))).is_err());
+    }
+}
@@ -18,6 +18,24 @@ pub enum ApolloError {
     UtilsError(#[from] UtilsError),
     #[error("Not enough cycles, required: {0}, available: {1}")]
     NotEnoughCycles(u128, u128),
+    #[error("Caller is not the apollo instance of the chain: {0}")]
+    CallerIsNotApolloInstance(Nat),
+    #[error("Wasm version not found: {0}")]
+    WasmVersionNotFound(u32),
+    #[error("No previous wasm version of the chain: {0}")]
+    NoPreviousWasmVersion(Nat),
+    #[error("Upgrade skipped, the canary chain failed: {0}")]
+    CanaryUpgradeFailed(Nat),
+    #[error("No instance wasm was uploaded")]
+    NoInstanceWasm,
+    #[error("Invalid wasm chunk offset, expected: {0}, got: {1}")]
+    InvalidWasmChunkOffset(u64, u64),
+    #[error("Uploaded module is not a wasm")]
+    InvalidWasm,
+    #[error("Wasm version {0} is installed on the chain {1}")]
+    WasmVersionInstalled(u32, Nat),
+    #[error("Wasm version {0} is the latest one")]
+    LatestWasmVersion(u32),
 }
 
 #[derive(Error, Debug, CandidType, Deserialize)]
@@ -30,6 +48,10 @@ pub enum ApolloInstanceError {
     FailedToCreate(String),
     #[error("Failed to stop: {0}")]
     FailedToStop(String),
+    #[error("Failed to start: {0}")]
+    FailedToStart(String),
+    #[error("Failed to get randomness: {0}")]
+    FailedToGetRandomness(String),
     #[error("Failed to delete: {0}")]
     FailedToDelete(String),
     #[error("Failed to install code: {0}")]
@@ -52,6 +74,22 @@ pub enum ApolloInstanceError {
     FailedToRestartTimer(String),
     #[error("Failed to get canister status: {0}")]
     FailedToGetCanisterStatus(String),
+    #[error("Invalid callback signature: {0}")]
+    InvalidCallbackSignature(String),
+    #[error("Callback data doesn't match the signature: {0}")]
+    CallbackDataMismatch(String),
+    #[error("Contract {0} is not granted to the user")]
+    ContractIsNotGranted(String),
+    #[error("Subscription not found: {0}")]
+    SubscriptionNotFound(u64),
+    #[error("Invalid subscription: {0}")]
+    InvalidSubscription(String),
+    #[error("Failed to relay request: {0}")]
+    FailedToRelayRequest(String),
+    #[error("Coordinator already exists: {0}")]
+    CoordinatorAlreadyExists(String),
+    #[error("Coordinator not found: {0}")]
+    CoordinatorNotFound(String),
 }
 
 #[derive(Error, Debug, CandidType, PartialEq, Deserialize)]
@@ -90,6 +128,10 @@ pub enum Web3Error {
     UnableToEstimateGas(String),
     #[error("Unable to sign contract call: {0}")]
     UnableToSignContractCall(String),
+    #[error("Unable to sign transaction: {0}")]
+    UnableToSignTx(String),
+    #[error("Unable to sign message: {0}")]
+    UnableToSignMessage(String),
     #[error("Unable to execute raw transaction: {0}")]
     UnableToExecuteRawTx(String),
     #[error("Unable to get tx receipt: {0}")]
@@ -118,7 +160,7 @@ pub enum Web3Error {
     UtilsError(#[from] UtilsError),
 }
 
-#[derive(Error, Debug, CandidType, Deserialize)]
+#[derive(Error, Debug, Clone, CandidType, Deserialize)]
 pub enum MulticallError {
     #[error("Invalid multicall result")]
     InvalidMulticallResult,
@@ -138,6 +180,8 @@ pub enum MulticallError {
     UnableToEncodeCallData(String),
     #[error("Block gas limit is too low")]
     BlockGasLimitIsTooLow,
+    #[error("Multicall results count mismatch, expected: {0}, actual: {1}")]
+    ResultsCountMismatch(u64, u64),
 }
 
 #[derive(Error, Debug, CandidType, PartialEq, Deserialize)]
@@ -164,7 +208,7 @@ pub enum WithdrawRequestsError {
     UtilsError(#[from] UtilsError),
 }
 
-#[derive(Error, Debug, CandidType, PartialEq, Deserialize)]
+#[derive(Error, Debug, Clone, CandidType, PartialEq, Deserialize)]
 pub enum SybilError {
     #[error("Unsuppored Asset Data Type: {0}")]
     UnsupportedAssetDataType(String),
@@ -173,3 +217,19 @@ pub enum SybilError {
     #[error("Invalid principal: {0}")]
     InvalidPrincipal(String),
 }
+
+#[derive(Error, Debug, Clone, CandidType, PartialEq, Deserialize)]
+pub enum DataSourceError {
+    #[error("Invalid feed id: {0}")]
+    InvalidFeedId(String),
+    #[error("Sybil error: {0}")]
+    SybilError(#[from] SybilError),
+    #[error("Canister error: {0}")]
+    CanisterError(String),
+    #[error("Http error: {0}")]
+    HttpError(String),
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+    #[error("Data is stale, timestamp: {0}, max staleness: {1} sec")]
+    StaleData(u64, u64),
+}
@@ -1,6 +1,7 @@
 pub mod address;
 pub mod apollo_instance;
 pub mod canister;
+pub mod data_source;
 pub mod encoding;
 pub mod errors;
 pub mod http;
@@ -2,8 +2,8 @@ use std::str::FromStr;
 
 use ic_web3_rs::{
     contract::{tokens::Tokenizable, Contract, Error, Options},
-    ethabi::{self, Event, RawLog, Token},
-    types::{H160, H256, U256},
+    ethabi::{self, ParamType, RawLog, Token},
+    types::{TransactionReceipt, H160, H256, U256},
     Transport,
 };
 
@@ -11,7 +11,7 @@ use crate::{
     address,
     errors::{MulticallError, Web3Error},
     log,
-    web3::Web3Instance,
+    web3::{SentTransaction, Web3Instance},
 };
 
 const MULTICALL_ABI: &[u8] = include_bytes!("../../../assets/MulticallABI.json");
@@ -22,13 +22,32 @@ const MULTICALL_EXECUTED_TOPIC: &str =
 const MULTICALL_EXECUTED_EVENT_NAME: &str = "MulticallExecuted";
 pub const BASE_GAS: u64 = 27_000;
 const GAS_FOR_OPS: u64 = 10_000;
+const GAS_PER_ZERO_BYTE: u64 = 4;
+const GAS_PER_NON_ZERO_BYTE: u64 = 16;
+// estimation is done against the current state, so the callbacks may use a bit more gas on execution
+const GAS_ESTIMATION_MARGIN_PERCENT: u64 = 20;
 pub const GAS_PER_TRANSFER: u64 = 7_900;
+// selector of `Error(string)`, used by `revert` and `require`
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+// selector of `Panic(uint256)`, used on failed asserts, overflows, etc.
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
 
 #[derive(Debug, Clone, Default)]
 pub struct Call {
     pub target: H160,
     pub call_data: Vec<u8>,
     pub gas_limit: U256,
+    pub request_id: u64,
+    /// The callback was paid on another chain, so the requester is not charged for it
+    pub prepaid: bool,
+    /// Fee, which is charged on top of the used gas
+    pub fee: U256,
+    /// Surcharge for the cycles, which were attached to the calls of the request
+    pub surcharge: U256,
+    /// Amount, which was deducted from the requester's balance before the call was sent
+    pub reserved: U256,
+    /// Cycles, which the instance spent on the request, it is reported, but not billed
+    pub cycles: u128,
 }
 
 impl Tokenizable for Call {
@@ -48,6 +67,13 @@ impl Tokenizable for Call {
                     target,
                     call_data,
                     gas_limit,
+                    request_id: 0,
+                    prepaid: false,
+                    fee: U256::zero(),
+                    surcharge: U256::zero(),
+                    reserved: U256::zero(),
+                    reserved: U256::zero(),
+                    cycles: 0,
                 });
             }
         }
@@ -98,11 +124,56 @@ impl Tokenizable for MulticallResult {
     fn into_token(self) -> Token {
         Token::Tuple(vec![
             Token::Bool(self.success),
+            Token::Uint(self.used_gas),
             Token::Bytes(self.return_data),
         ])
     }
 }
 
+impl MulticallResult {
+    /// Human-readable reason of the callback revert, `None` if the callback succeeded
+    pub fn revert_reason(&self) -> Option<String> {
+        if self.success {
+            return None;
+        }
+
+        Some(decode_revert_reason(&self.return_data))
+    }
+}
+
+/// Decodes the return data of a reverted call.
+/// Unknown formats, e.g. custom errors, are returned as hex
+pub fn decode_revert_reason(return_data: &[u8]) -> String {
+    if return_data.is_empty() {
+        return "reverted without a reason".to_string();
+    }
+
+    if return_data.len() >= 4 {
+        let (selector, data) = return_data.split_at(4);
+
+        let reason = if selector == ERROR_SELECTOR {
+            ethabi::decode(&[ParamType::String], data)
+                .ok()
+                .and_then(|tokens| tokens.into_iter().next())
+                .and_then(Token::into_string)
+        } else if selector == PANIC_SELECTOR {
+            ethabi::decode(&[ParamType::Uint(256)], data)
+                .ok()
+                .and_then(|tokens| tokens.into_iter().next())
+                .and_then(Token::into_uint)
+                .map(|code| format!("panic: 0x{:x}", code))
+        } else {
+            None
+        };
+
+        if let Some(reason) = reason {
+            return reason;
+        }
+    }
+
+    format!("0x{}", hex::encode(return_data))
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Transfer {
     pub target: H160,
@@ -229,53 +300,70 @@ impl MulticallArgs {
     }
 }
 
+/// Sends the calls in batches, each batch in a separate transaction, without waiting
+/// for the confirmations. Batches get consecutive nonces starting from `nonce`,
+/// every sent transaction is reported to `on_sent` alongside the calls it executes.
+/// Returns the calls, which exceed the block gas limit and therefore were not sent
+#[allow(clippy::too_many_arguments)]
 pub async fn multicall<T: Transport>(
     w3: &Web3Instance<T>,
     multicall_address: &str,
     from: String,
-    mut calls: Vec<Call>,
+    calls: Vec<Call>,
     key_name: String,
     chain_id: u64,
     block_gas_limit: U256,
     gas_price: &U256,
-) -> Result<Vec<MulticallResult>, MulticallError> {
+    mut nonce: U256,
+    on_sent: impl Fn(SentTransaction, Vec<Call>),
+) -> Result<Vec<Call>, MulticallError> {
     log!("[MULTICALL] chain: {}, multicall started", chain_id);
 
-    let mut result: Vec<MulticallResult> = vec![];
+    // such calls can't be included in any batch, they are returned to the caller
+    let max_call_gas = block_gas_limit.saturating_sub(U256::from(BASE_GAS + GAS_FOR_OPS));
+    let (mut calls, rejected_calls): (Vec<_>, Vec<_>) = calls
+        .into_iter()
+        .partition(|call| call_gas_upper_bound(call) <= max_call_gas);
+
+    for call in &rejected_calls {
+        log!(
+            "[MULTICALL] chain: {}, call to {} rejected, gas limit {} exceeds the block gas limit",
+            chain_id,
+            call.target,
+            call.gas_limit
+        );
+    }
 
     let contract_addr = address::to_h160(multicall_address)?;
     let contract = Contract::from_json(w3.eth(), contract_addr, MULTICALL_ABI)
         .map_err(|err| Web3Error::UnableToCreateContract(err.to_string()))?;
 
-    let multicall_abi = ethabi::Contract::load(MULTICALL_ABI).unwrap();
-
-    let event = multicall_abi
-        .event(MULTICALL_EXECUTED_EVENT_NAME)
-        .expect("should be able to get event by name");
-
     while !calls.is_empty() {
         let (current_calls_batch, _calls) = get_current_calls_batch(&calls, block_gas_limit);
         calls = _calls;
 
-        result.append(
-            &mut execute_multicall_batch(
-                w3,
-                from.clone(),
-                &gas_price,
-                &contract,
-                MulticallArgs::new(current_calls_batch),
-                chain_id,
-                key_name.clone(),
-                event,
-            )
-            .await?,
-        );
+        let sent_tx = send_multicall_batch(
+            w3,
+            from.clone(),
+            gas_price,
+            &contract,
+            MulticallArgs::new(current_calls_batch.clone()),
+            chain_id,
+            key_name.clone(),
+            nonce,
+        )
+        .await?;
+
+        on_sent(sent_tx, current_calls_batch);
+
+        nonce += U256::one();
     }
 
-    Ok(result)
+    Ok(rejected_calls)
 }
 
-async fn execute_multicall_batch<T: Transport>(
+#[allow(clippy::too_many_arguments)]
+async fn send_multicall_batch<T: Transport>(
     w3: &Web3Instance<T>,
     from: String,
     gas_price: &U256,
@@ -283,78 +371,30 @@ async fn execute_multicall_batch<T: Transport>(
     multicall_args: MulticallArgs,
     chain_id: u64,
     key_name: String,
-    event: &Event,
-) -> Result<Vec<MulticallResult>, MulticallError> {
+    nonce: U256,
+) -> Result<SentTransaction, MulticallError> {
     log!(
-        "[MULTICALL] chain: {}, multicall batch started, calls: {}",
+        "[MULTICALL] chain: {}, multicall batch started, calls: {}, nonce: {}",
         chain_id,
-        multicall_args.calls.len()
+        multicall_args.calls.len(),
+        nonce
     );
 
-    let options = Options {
-        gas_price: Some(*gas_price),
-        nonce: Some(w3.get_nonce(&from).await?),
-        gas: Some(
-            multicall_args
-                .calls
-                .iter()
-                .fold(U256::from(BASE_GAS + GAS_FOR_OPS), |result, call| {
-                    result + call.gas_limit
-                }),
-        ),
-        ..Default::default()
-    };
+    let gas = estimate_multicall_batch(w3, contract, &multicall_args, &from, gas_price).await;
 
-    log!("[MULTICALL] estimating gas for multicall");
-
-    // TODO: maybe implement better gas estimation
-
-    // let mut estimated_gas = Web3Instance::estimate_gas(
-    //     contract,
-    //     MULTICALL_CALL_FUNCTION,
-    //     multicall_args.clone(),
-    //     &from,
-    //     &options,
-    // )
-    // .await?;
-
-    // log!("[MULTICALL] gas_price: {}", gas_price);
-    // log!("[MULTICALL] estimated gas: {}", estimated_gas);
-
-    // let additional_gas = multicall_args
-    //     .calls
-    //     .iter()
-    //     .fold(U256::from(0), |sum, c| sum + c.gas_limit);
-    // TODO: ic users gas_limit will be too big, error
-    // `RPC error: Error { code: ServerError(-32000), message: "tx fee (12.85 ether) exceeds the configured cap (1.00 ether)", data: None }`
-    // will be returned
-
-    // log!("[MULTICALL] additional gas: {}", additional_gas);
-
-    // estimated_gas = estimated_gas + additional_gas;
-
-    // options.gas = Some(estimated_gas);
-    // log!(
-    //     "[MULTICALL] chain: {}, estimated gas & limit: {}",
-    //     chain_id,
-    //     options.gas.unwrap()
-    // );
-
-    let params = vec![multicall_args.clone().into_token()];
-
-    let signed_call = w3
-        .sign(
-            contract,
-            MULTICALL_CALL_FUNCTION,
-            params.clone(),
-            options,
-            from,
-            key_name,
-            chain_id,
-        )
-        .await?;
+    log!(
+        "[MULTICALL] chain: {}, gas limit of the batch: {}",
+        chain_id,
+        gas
+    );
+
+    let call_data = contract
+        .abi()
+        .function(MULTICALL_CALL_FUNCTION)
+        .and_then(|f| f.encode_input(&[multicall_args.clone().into_token()]))
+        .map_err(|err| MulticallError::UnableToEncodeCallData(err.to_string()))?;
 
-    for call in multicall_args.calls {
+    for call in &multicall_args.calls {
         log!(
             "[MULTICALL] chain: {}, call to: {}, user's gas: {}",
             chain_id,
@@ -363,29 +403,55 @@ async fn execute_multicall_batch<T: Transport>(
         );
     }
 
-    log!("[MULTICALL] chain: {}, tx was signed", chain_id);
+    let sent_tx = w3
+        .send_transaction(
+            contract.address(),
+            call_data,
+            U256::zero(),
+            gas,
+            *gas_price,
+            nonce,
+            from,
+            key_name,
+            chain_id,
+        )
+        .await?;
 
-    let tx_hash = w3.send_raw_transaction_and_wait(signed_call).await?;
+    log!(
+        "[MULTICALL] chain: {}, tx was sent: {:?}",
+        chain_id,
+        sent_tx.tx_hash
+    );
 
-    log!("[MULTICALL] chain: {}, tx was executed", chain_id);
+    Ok(sent_tx)
+}
 
-    let logs = w3
-        .get_logs(
-            tx_hash.block_number.expect("should be present").as_u64(),
-            Some(tx_hash.block_number.expect("should be present").as_u64()),
-            Some(vec![
-                H256::from_str(MULTICALL_EXECUTED_TOPIC).expect("should be able to parse")
-            ]),
-            Some(contract.address()),
-        )
-        .await?;
+/// Parses results of the calls from the `MulticallExecuted` event of the mined multicall transaction.
+/// Only the receipt's own logs are used, so results of other multicalls in the same block are ignored
+pub fn get_multicall_results(
+    multicall_address: &str,
+    receipt: &TransactionReceipt,
+    expected_count: usize,
+) -> Result<Vec<MulticallResult>, MulticallError> {
+    let multicall_abi = ethabi::Contract::load(MULTICALL_ABI).unwrap();
+
+    let event = multicall_abi
+        .event(MULTICALL_EXECUTED_EVENT_NAME)
+        .expect("should be able to get event by name");
+
+    let multicall_address = address::to_h160(multicall_address)?;
+    let topic = H256::from_str(MULTICALL_EXECUTED_TOPIC).expect("should be able to parse");
 
     let mut multicall_results = Vec::new();
 
-    for log in logs {
+    for log in receipt
+        .logs
+        .iter()
+        .filter(|log| log.address == multicall_address && log.topics.first() == Some(&topic))
+    {
         let raw_log = RawLog {
-            topics: log.topics,
-            data: log.data.0,
+            topics: log.topics.clone(),
+            data: log.data.0.clone(),
         };
 
         let parsed_log = event
@@ -406,14 +472,100 @@ async fn execute_multicall_batch<T: Transport>(
         }
     }
 
+    if multicall_results.len() != expected_count {
+        return Err(MulticallError::ResultsCountMismatch(
+            expected_count as u64,
+            multicall_results.len() as u64,
+        ));
+    }
+
     Ok(multicall_results)
 }
 
+/// Estimates the gas of the batch with `eth_estimateGas` and adds a safety margin.
+/// The result never exceeds the upper bound, in which every callback uses its whole gas limit,
+/// and falls back to the upper bound if the estimation fails
+async fn estimate_multicall_batch<T: Transport>(
+    w3: &Web3Instance<T>,
+    contract: &Contract<T>,
+    multicall_args: &MulticallArgs,
+    from: &str,
+    gas_price: &U256,
+) -> U256 {
+    let upper_bound = batch_gas_upper_bound(&multicall_args.calls);
+
+    let options = Options {
+        gas_price: Some(*gas_price),
+        ..Default::default()
+    };
+
+    let estimated_gas = match Web3Instance::estimate_gas(
+        contract,
+        MULTICALL_CALL_FUNCTION,
+        multicall_args.clone(),
+        from,
+        &options,
+    )
+    .await
+    {
+        Ok(estimated_gas) => estimated_gas,
+        Err(err) => {
+            log!(
+                "[MULTICALL] gas estimation failed, falling back to the upper bound {}: {}",
+                upper_bound,
+                err
+            );
+
+            return upper_bound;
+        }
+    };
+
+    let with_margin = estimated_gas * (100 + GAS_ESTIMATION_MARGIN_PERCENT) / 100;
+
+    log!(
+        "[MULTICALL] estimated gas: {}, with margin: {}, upper bound: {}",
+        estimated_gas,
+        with_margin,
+        upper_bound
+    );
+
+    with_margin.min(upper_bound)
+}
+
+/// Gas of the batch if every callback uses its whole gas limit
+fn batch_gas_upper_bound(calls: &[Call]) -> U256 {
+    calls
+        .iter()
+        .fold(U256::from(BASE_GAS + GAS_FOR_OPS), |result, call| {
+            result + call_gas_upper_bound(call)
+        })
+}
+
+/// Callback gas limit plus the intrinsic cost of the call's calldata in the multicall transaction
+fn call_gas_upper_bound(call: &Call) -> U256 {
+    let encoded_call = ethabi::encode(&[call.clone().into_token()]);
+
+    call.gas_limit + calldata_gas(&encoded_call)
+}
+
+fn calldata_gas(data: &[u8]) -> U256 {
+    let gas = data.iter().fold(0, |gas, byte| {
+        gas + if *byte == 0 {
+            GAS_PER_ZERO_BYTE
+        } else {
+            GAS_PER_NON_ZERO_BYTE
+        }
+    });
+
+    U256::from(gas)
+}
+
+/// Splits the calls into the first batch, which fits into the block gas limit, and the rest
 fn get_current_calls_batch(calls: &[Call], block_gas_limit: U256) -> (Vec<Call>, Vec<Call>) {
-    let mut gas_counter = U256::from(BASE_GAS + 1000);
+    let mut gas_counter = U256::from(BASE_GAS + GAS_FOR_OPS);
     for (i, call) in calls.iter().enumerate() {
-        gas_counter += call.gas_limit;
-        if gas_counter >= block_gas_limit {
+        gas_counter += call_gas_upper_bound(call);
+        if gas_counter > block_gas_limit {
             return (calls[..i].to_vec(), calls[i..].to_vec());
         }
     }
@@ -451,6 +603,8 @@ pub async fn estimate_multitransfer<T: Transport>(
 }
 
 // TODO: reread this function and make sure it's correct
+/// Sends the transfers in a single transaction without waiting for the confirmation
+#[allow(clippy::too_many_arguments)]
 pub async fn multitransfer<T: Transport>(
     w3: &Web3Instance<T>,
     gas_price: U256,
@@ -460,46 +614,121 @@ pub async fn multitransfer<T: Transport>(
     multicall_address: &str,
     from: String,
     key_name: String,
-) -> Result<(), MulticallError> {
+    nonce: U256,
+) -> Result<SentTransaction, MulticallError> {
     let contract_addr = address::to_h160(multicall_address)?;
     let contract = Contract::from_json(w3.eth(), contract_addr, MULTICALL_ABI)
         .map_err(|err| Web3Error::UnableToCreateContract(err.to_string()))?;
 
-    let params = vec![multitransfer_args.clone().into_token()];
-
-    let nonce = w3.get_nonce(&from).await?;
-
-    let options = Options {
-        gas_price: Some(gas_price),
-        gas: Some(estimated_gas),
-        nonce: Some(nonce),
-        value: Some(
-            multitransfer_args
-                .transfers
-                .iter()
-                .fold(U256::from(0), |sum, t| sum + t.value),
-        ),
-        ..Default::default()
-    };
-
-    let signed_call = w3
-        .sign(
-            &contract,
-            &MULTICALL_TRANSFER_FUNCTION,
-            params,
-            options,
+    let call_data = contract
+        .abi()
+        .function(MULTICALL_TRANSFER_FUNCTION)
+        .and_then(|f| f.encode_input(&[multitransfer_args.clone().into_token()]))
+        .map_err(|err| MulticallError::UnableToEncodeCallData(err.to_string()))?;
+
+    let value = multitransfer_args
+        .transfers
+        .iter()
+        .fold(U256::from(0), |sum, t| sum + t.value);
+
+    let sent_tx = w3
+        .send_transaction(
+            contract_addr,
+            call_data,
+            value,
+            estimated_gas,
+            gas_price,
+            nonce,
             from,
             key_name,
             chain_id,
         )
         .await?;
 
-    log!("[Multitransfer] tx send, chain_id: {}", chain_id);
+    log!(
+        "[Multitransfer] tx send, chain_id: {}, tx: {:?}",
+        chain_id,
+        sent_tx.tx_hash
+    );
 
-    let tx = w3.send_raw_transaction_and_wait(signed_call).await?;
-    log!("TX: {:?}", tx.transaction_hash);
+    Ok(sent_tx)
+}
 
-    log!("[Multitransfer] tx received, chain_id: {}", chain_id);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(gas_limit: u64) -> Call {
+        Call {
+            target: H160::repeat_byte(1),
+            call_data: vec![1, 0, 2, 0],
+            gas_limit: U256::from(gas_limit),
+            request_id: 0,
+            prepaid: false,
+            fee: U256::zero(),
+            surcharge: U256::zero(),
+            reserved: U256::zero(),
+            cycles: 0,
+        }
+    }
+
+    #[test]
+    fn test_decode_revert_reason() {
+        let mut error = ERROR_SELECTOR.to_vec();
+        error.extend(ethabi::encode(&[Token::String("not allowed".into())]));
+        assert_eq!(decode_revert_reason(&error), "not allowed");
+
+        let mut panic = PANIC_SELECTOR.to_vec();
+        panic.extend(ethabi::encode(&[Token::Uint(U256::from(0x11))]));
+        assert_eq!(decode_revert_reason(&panic), "panic: 0x11");
+
+        assert_eq!(decode_revert_reason(&[]), "reverted without a reason");
+        assert_eq!(decode_revert_reason(&[0xab, 0xcd]), "0xabcd");
+        assert_eq!(
+            decode_revert_reason(&[0x12, 0x34, 0x56, 0x78]),
+            "0x12345678"
+        );
+    }
+
+    #[test]
+    fn test_multicall_result_token_roundtrip() {
+        let result = MulticallResult {
+            success: false,
+            used_gas: U256::from(21_000),
+            return_data: vec![1, 2, 3],
+        };
+
+        let parsed = MulticallResult::from_token(result.clone().into_token()).unwrap();
+        assert_eq!(parsed.success, result.success);
+        assert_eq!(parsed.used_gas, result.used_gas);
+        assert_eq!(parsed.return_data, result.return_data);
+    }
 
-    Ok(())
+    #[test]
+    fn test_calldata_gas() {
+        assert_eq!(calldata_gas(&[]), U256::zero());
+        assert_eq!(calldata_gas(&[0, 0, 1]), U256::from(4 + 4 + 16));
+    }
+
+    #[test]
+    fn test_get_current_calls_batch() {
+        let calls = vec![call(100_000), call(100_000), call(100_000)];
+        let call_gas = call_gas_upper_bound(&calls[0]);
+        let block_gas_limit = U256::from(BASE_GAS + GAS_FOR_OPS) + call_gas * 2;
+
+        let (batch, rest) = get_current_calls_batch(&calls, block_gas_limit);
+        assert_eq!(batch.len(), 2);
+        assert_eq!(rest.len(), 1);
+
+        let (batch, rest) = get_current_calls_batch(&rest, block_gas_limit);
+        assert_eq!(batch.len(), 1);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn test_batch_gas_upper_bound_includes_calldata() {
+        let calls = vec![call(100_000)];
+
+        assert!(batch_gas_upper_bound(&calls) > U256::from(BASE_GAS + GAS_FOR_OPS + 100_000));
+    }
 }
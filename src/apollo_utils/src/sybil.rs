@@ -35,6 +35,17 @@ pub enum SybilAssetData {
     },
 }
 
+impl SybilAssetData {
+    /// Timestamp of the price feed data, other types of data are not timestamped
+    pub fn timestamp(&self) -> Option<u64> {
+        match self {
+            SybilAssetData::DefaultPriceFeed { timestamp, .. }
+            | SybilAssetData::CustomPriceFeed { timestamp, .. } => Some(*timestamp),
+            _ => None,
+        }
+    }
+}
+
 /// Result of the asset data request from sybil
 /// Copy-pasted from sybil in order to avoid lots of imports
 #[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
@@ -83,10 +94,11 @@ async fn get_asset_data(
     }
 }
 
+/// Returns the feed data alongside the Sybil signature, so consumers can verify it on-chain
 pub async fn get_sybil_feed(
     sybil_canister_address: String,
     feed_id: String,
-) -> Result<SybilAssetData, SybilError> {
+) -> Result<AssetDataResult, SybilError> {
     let sybil_canister_address = Principal::from_text(sybil_canister_address)
         .map_err(|err| SybilError::InvalidPrincipal(err.to_string()))?;
 
@@ -98,5 +110,5 @@ pub async fn get_sybil_feed(
     let asset_data = retry_until_success!(get_asset_data(sybil_canister_address, feed_id.clone()))?;
 
     log!("[ABI] get_sybil_input got asset_data feed_id: {}", feed_id);
-    Ok(asset_data.data)
+    Ok(asset_data)
 }
@@ -1,6 +1,9 @@
 use anyhow::Result;
 use candid::Principal;
-use ic_cdk::api::management_canister::http_request::{TransformContext, TransformFunc};
+use ic_cdk::api::management_canister::{
+    ecdsa::{sign_with_ecdsa, EcdsaCurve, EcdsaKeyId, SignWithEcdsaArgument},
+    http_request::{TransformContext, TransformFunc},
+};
 use ic_web3_rs::{
     api::Eth,
     contract::{
@@ -9,23 +12,26 @@ use ic_web3_rs::{
     },
     ethabi::Token,
     ic::KeyInfo,
+    signing::recover,
     transports::ic_http_client::CallOptionsBuilder,
     types::{
         BlockId, BlockNumber, Bytes, CallRequest, FilterBuilder, Log, SignedTransaction,
-        Transaction, TransactionId, TransactionReceipt, H160, H256, U256, U64,
+        Transaction, TransactionId, TransactionParameters, TransactionReceipt, H160, H256, U256,
+        U64,
     },
     Transport, Web3,
 };
 use std::{str::FromStr, time::Duration};
 
 use crate::{
+    address,
     errors::{UtilsError, Web3Error},
     http, log, retry_until_success, time,
 };
 
 use self::evm_canister_transport::EVMCanisterTransport;
 
-const ECDSA_SIGN_CYCLES: u64 = 23_000_000_000;
+pub const ECDSA_SIGN_CYCLES: u64 = 23_000_000_000;
 pub const TRANSFER_GAS_LIMIT: u64 = 21_000;
 const TX_SUCCESS_STATUS: u64 = 1;
 const TX_WAIT_DELAY: Duration = Duration::from_secs(3);
@@ -37,6 +43,19 @@ pub struct Web3Instance<T: Transport> {
     w3: Web3<T>,
 }
 
+/// Transaction sent from the AMA, with everything needed to re-sign it
+/// using the same nonce (speed up or cancellation)
+#[derive(Debug, Clone, Default)]
+pub struct SentTransaction {
+    pub tx_hash: H256,
+    pub nonce: U256,
+    pub to: H160,
+    pub data: Vec<u8>,
+    pub value: U256,
+    pub gas: U256,
+    pub gas_price: U256,
+}
+
 pub fn instance(
     rpc_url: String,
     evm_rpc_canister: String,
@@ -53,6 +72,41 @@ pub fn instance(
     // )))
 }
 
+/// Signs the 32-byte hash with the AMA key. The signature is returned in the `r || s || v` format,
+/// so it can be verified on-chain with `ecrecover`
+pub async fn sign_message_hash(
+    message_hash: [u8; 32],
+    key_name: String,
+    signer: &str,
+) -> Result<Vec<u8>, Web3Error> {
+    let (response,) = sign_with_ecdsa(SignWithEcdsaArgument {
+        message_hash: message_hash.to_vec(),
+        derivation_path: vec![ic_cdk::id().as_slice().to_vec()],
+        key_id: EcdsaKeyId {
+            curve: EcdsaCurve::Secp256k1,
+            name: key_name,
+        },
+    })
+    .await
+    .map_err(|(_, err)| Web3Error::UnableToSignMessage(err))?;
+
+    let signer = address::to_h160(signer)?;
+    let mut signature = response.signature;
+
+    // the management canister doesn't return the recovery id, so it is found by recovering the signer
+    let recovery_id = (0..2)
+        .find(|recovery_id| {
+            recover(&message_hash, &signature, *recovery_id)
+                .map(|address| address == signer)
+                .unwrap_or_default()
+        })
+        .ok_or_else(|| Web3Error::UnableToSignMessage("unable to find recovery id".into()))?;
+
+    signature.push(27 + recovery_id as u8);
+
+    Ok(signature)
+}
+
 impl<T: Transport> Web3Instance<T> {
     pub fn new(w3: Web3<T>) -> Self {
         Self { w3 }
@@ -204,6 +258,80 @@ impl<T: Transport> Web3Instance<T> {
         Ok(estimated_gas)
     }
 
+    #[allow(clippy::too_many_arguments)]
+    pub async fn sign_transaction(
+        &self,
+        to: H160,
+        data: Vec<u8>,
+        value: U256,
+        gas: U256,
+        gas_price: U256,
+        nonce: U256,
+        from: String,
+        key_name: String,
+        chain_id: u64,
+    ) -> Result<SignedTransaction, Web3Error> {
+        let tx = TransactionParameters {
+            nonce: Some(nonce),
+            to: Some(to),
+            gas,
+            gas_price: Some(gas_price),
+            value,
+            data: Bytes(data),
+            ..Default::default()
+        };
+
+        let signed_tx = self
+            .w3
+            .accounts()
+            .sign_transaction(tx, from, Self::key_info(key_name), chain_id)
+            .await
+            .map_err(|err| Web3Error::UnableToSignTx(err.to_string()))?;
+
+        Ok(signed_tx)
+    }
+
+    /// Signs the transaction with the given nonce and sends it without waiting for the confirmation
+    #[allow(clippy::too_many_arguments)]
+    pub async fn send_transaction(
+        &self,
+        to: H160,
+        data: Vec<u8>,
+        value: U256,
+        gas: U256,
+        gas_price: U256,
+        nonce: U256,
+        from: String,
+        key_name: String,
+        chain_id: u64,
+    ) -> Result<SentTransaction, Web3Error> {
+        let signed_tx = self
+            .sign_transaction(
+                to,
+                data.clone(),
+                value,
+                gas,
+                gas_price,
+                nonce,
+                from,
+                key_name,
+                chain_id,
+            )
+            .await?;
+
+        let tx_hash = self.send_raw_transaction(signed_tx).await?;
+
+        Ok(SentTransaction {
+            tx_hash,
+            nonce,
+            to,
+            data,
+            value,
+            gas,
+            gas_price,
+        })
+    }
+
     pub async fn get_call_result(
         &self,
         contract: &Contract<T>,
@@ -272,6 +400,19 @@ impl<T: Transport> Web3Instance<T> {
         Ok(nonce)
     }
 
+    /// Returns the receipt of the transaction, or `None` if it is not mined yet
+    pub async fn get_tx_receipt(
+        &self,
+        tx_hash: &H256,
+    ) -> Result<Option<TransactionReceipt>, Web3Error> {
+        let tx_receipt = retry_until_success!(self
+            .eth()
+            .transaction_receipt(*tx_hash, http::transform_ctx()))
+        .map_err(|err| Web3Error::UnableToGetTxReceipt(err.to_string()))?;
+
+        Ok(tx_receipt.filter(|receipt| receipt.status.is_some()))
+    }
+
     pub async fn send_raw_transaction(
         &self,
         signed_call: SignedTransaction,